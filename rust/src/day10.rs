@@ -3,6 +3,12 @@
 /// Figure out what message appears as 2D vectors line up
 
 use regex::Regex;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
 
 /// A star in the sky that has position and velocity in 2 dimensions
 struct Star {
@@ -17,6 +23,12 @@ impl Star {
         self.x += self.vx;
         self.y += self.vy;
     }
+
+    /// Where this star will be `t` seconds from now (or was, if `t` is
+    /// negative)
+    pub fn position_at(&self, t: isize) -> (isize, isize) {
+        (self.x + self.vx*t, self.y + self.vy*t)
+    }
 }
 
 /// A night sky that has kinematic stars in it
@@ -51,6 +63,147 @@ impl Sky {
         }
     }
 
+    /// Where every star is `t` seconds from now.  Negative times work
+    /// too, and nothing gets moved.
+    pub fn positions_at(&self, t: isize) -> Vec<(isize, isize)> {
+        self.stars.iter().map(|star| star.position_at(t)).collect()
+    }
+
+    /// The smallest box holding every star at time `t`, as
+    /// (xmin, xmax, ymin, ymax)
+    pub fn bounds_at(&self, t: isize) -> (isize, isize, isize, isize) {
+        let positions = self.positions_at(t);
+        let xmin = positions.iter().map(|&(x, _y)| x).min().unwrap();
+        let xmax = positions.iter().map(|&(x, _y)| x).max().unwrap();
+        let ymin = positions.iter().map(|&(_x, y)| y).min().unwrap();
+        let ymax = positions.iter().map(|&(_x, y)| y).max().unwrap();
+        (xmin, xmax, ymin, ymax)
+    }
+
+    /// How much sky the bounding box covers at time `t`
+    pub fn area_at(&self, t: isize) -> isize {
+        let (xmin, xmax, ymin, ymax) = self.bounds_at(t);
+        (xmax - xmin + 1) * (ymax - ymin + 1)
+    }
+
+    /// Finds the time when the stars are bunched up the tightest, which
+    /// is when the message shows up
+    /// 
+    /// The bounding box shrinks as the stars come together and grows
+    /// after they pass each other, so this finds which way is
+    /// downhill, doubles its way out until it's uphill again, and then
+    /// ternary searches the stretch in between.
+    pub fn convergence_time(&self) -> isize {
+        let direction = if self.area_at(1) < self.area_at(0) {
+            1
+        } else if self.area_at(-1) < self.area_at(0) {
+            -1
+        } else {
+            return 0;
+        };
+        let area = |steps: isize| self.area_at(direction*steps);
+
+        let mut low = 0;
+        let mut high = 1;
+        while area(high*2) < area(high) {
+            low = high;
+            high *= 2;
+        }
+        high *= 2;
+
+        while high - low > 2 {
+            let third = (high - low) / 3;
+            if area(low + third) < area(high - third) {
+                high -= third;
+            } else {
+                low += third;
+            }
+        }
+        let best = (low..=high).min_by_key(|&steps| area(steps)).unwrap();
+        direction*best
+    }
+
+    /// Draws the sky at time `t`, cropped to the stars.  Stars are '#'
+    /// and empty sky is '.'
+    pub fn render_at(&self, t: isize) -> String {
+        self.render_with(t, &RenderOptions { margin: 0, ..RenderOptions::default() })
+    }
+
+    /// Draws the sky at time `t` as text, however `options` says to
+    pub fn render_with(&self, t: isize, options: &RenderOptions) -> String {
+        let mut result = String::new();
+        self.write_frame(&mut result, t, options).unwrap();
+        result
+    }
+
+    /// Draws the sky at time `t` as text into anything that can take it
+    pub fn write_frame<W: fmt::Write>(&self, out: &mut W, t: isize, options: &RenderOptions) -> fmt::Result {
+        let frame = self.frame_at(t, options.margin);
+        for y in 0..frame.height {
+            for x in 0..frame.width {
+                out.write_char(if frame.is_star(x, y) { options.star } else { options.empty })?;
+            }
+            out.write_char('\n')?;
+        }
+        Ok(())
+    }
+
+    /// Draws the sky at time `t` as a plain (P1) PBM image, with black
+    /// pixels for stars
+    pub fn write_pbm<W: io::Write>(&self, out: &mut W, t: isize, margin: usize) -> io::Result<()> {
+        let frame = self.frame_at(t, margin);
+        writeln!(out, "P1\n{} {}", frame.width, frame.height)?;
+        for y in 0..frame.height {
+            let row: Vec<&str> = (0..frame.width)
+                .map(|x| if frame.is_star(x, y) { "1" } else { "0" })
+                .collect();
+            writeln!(out, "{}", row.join(" "))?;
+        }
+        Ok(())
+    }
+
+    /// Writes one PBM image per second in `times` into `directory`,
+    /// named like "frame_3.pbm", and returns the paths in order
+    pub fn export_frames(&self, directory: &Path, times: RangeInclusive<isize>, margin: usize) -> io::Result<Vec<PathBuf>> {
+        let mut paths = vec![];
+        for t in times {
+            let path = directory.join(format!("frame_{}.pbm", t));
+            let mut file = BufWriter::new(File::create(&path)?);
+            self.write_pbm(&mut file, t, margin)?;
+            file.flush()?;
+            paths.push(path);
+        }
+        Ok(paths)
+    }
+
+    /// Marks where the stars are at time `t` in a grid covering the
+    /// bounding box plus `margin` on every side
+    fn frame_at(&self, t: isize, margin: usize) -> Frame {
+        let (xmin, xmax, ymin, ymax) = self.bounds_at(t);
+        let margin = margin as isize;
+        let left = xmin - margin;
+        let top = ymin - margin;
+        let width = (xmax - xmin + 1 + 2*margin) as usize;
+        let height = (ymax - ymin + 1 + 2*margin) as usize;
+        let mut stars = vec![false; width*height];
+        for (x, y) in self.positions_at(t) {
+            stars[(x - left) as usize + (y - top) as usize * width] = true;
+        }
+        Frame { width, height, stars }
+    }
+
+    /// Finds when the message shows up and what it looks like
+    pub fn message(&self) -> (isize, String) {
+        let t = self.convergence_time();
+        (t, self.render_at(t))
+    }
+
+    /// Finds the message and reads it with `glyphs`
+    pub fn read_message(&self, glyphs: &GlyphTable) -> Result<String, OcrError> {
+        let (_t, frame) = self.message();
+        glyphs.recognize(&frame)
+    }
+
     /// Loop over time, updating the Sky and potentially displaying the
     /// current state.  Only displays if the stars are clustered enough
     /// together.  Stars are '#' and empty sky is '.'
@@ -58,27 +211,260 @@ impl Sky {
         for t in 0..frames {
             self.update();
 
-            let xmin = self.stars.iter().map(|star| star.x).min().unwrap();
-            let xmax = self.stars.iter().map(|star| star.x).max().unwrap();
-            let ymin = self.stars.iter().map(|star| star.y).min().unwrap();
-            let ymax = self.stars.iter().map(|star| star.y).max().unwrap();
+            let (_xmin, _xmax, ymin, ymax) = self.bounds_at(0);
 
             // Only display if the bounds are reasonable
             if (ymax - ymin) > 30 {
                 continue;
             }
-            println!("");
+            println!();
             println!("t = {}", t);
-            for y in (ymin - 5)..=(ymax + 5) {
-                for x in (xmin - 5)..=(xmax + 5) {
-                    if self.stars.iter().any(|star| star.x == x && star.y == y) {
-                        print!("#");
-                    } else {
-                        print!(".");
-                    }
-                }
-                println!("");
+            print!("{}", self.render_with(0, &RenderOptions::default()));
+        }
+    }
+}
+
+/// How to draw the sky as text
+pub struct RenderOptions {
+    pub star: char,
+    pub empty: char,
+    /// How much empty sky to leave around the stars
+    pub margin: usize,
+}
+
+impl Default for RenderOptions {
+    /// Stars are '#', empty sky is '.', with 5 spaces of margin
+    fn default() -> Self {
+        Self { star: '#', empty: '.', margin: 5 }
+    }
+}
+
+/// A snapshot of which spots in a patch of sky have stars in them
+struct Frame {
+    width: usize,
+    height: usize,
+    stars: Vec<bool>,
+}
+
+impl Frame {
+    fn is_star(&self, x: usize, y: usize) -> bool {
+        self.stars[x + y*self.width]
+    }
+}
+
+/// Things that can stop a frame from being read as text
+#[derive(Debug, PartialEq)]
+pub enum OcrError {
+    /// The frame isn't as tall as the glyphs
+    WrongHeight { expected: usize, found: usize },
+    /// Some glyphs weren't in the table.  Each one comes with its
+    /// position in the message and its bitmap, one row per line.
+    Unrecognised(Vec<(usize, String)>),
+}
+
+/// Letter shapes for reading star messages, all the same size, laid
+/// out left to right with a fixed gap between them
+/// 
+/// Bitmaps use '#' for a star and '.' for empty sky.
+pub struct GlyphTable {
+    width: usize,
+    height: usize,
+    spacing: usize,
+    glyphs: HashMap<Vec<String>, char>,
+}
+
+impl GlyphTable {
+    /// An empty table for glyphs `width` by `height` with `spacing`
+    /// empty columns between them
+    pub fn new(width: usize, height: usize, spacing: usize) -> Self {
+        Self { width, height, spacing, glyphs: HashMap::new() }
+    }
+
+    /// The 6x10 font the puzzle uses, as far as it's known
+    pub fn aoc() -> Self {
+        let mut table = GlyphTable::new(6, 10, 2);
+        for (letter, bitmap) in AOC_GLYPHS.iter() {
+            table.insert(*letter, bitmap);
+        }
+        table
+    }
+
+    /// Adds (or replaces) the letter for a bitmap, one row per line
+    pub fn insert(&mut self, letter: char, bitmap: &str) {
+        let rows = bitmap.lines().map(|row| row.trim().to_string()).collect();
+        self.glyphs.insert(rows, letter);
+    }
+
+    /// Reads a rendered frame, cropped so that the first glyph starts
+    /// in the first column
+    pub fn recognize(&self, frame: &str) -> Result<String, OcrError> {
+        let rows: Vec<Vec<char>> = frame.lines().map(|row| row.chars().collect()).collect();
+        if rows.len() != self.height {
+            return Err(OcrError::WrongHeight { expected: self.height, found: rows.len() });
+        }
+
+        let frame_width = rows.iter().map(|row| row.len()).max().unwrap_or(0);
+        let stride = self.width + self.spacing;
+        let mut result = String::new();
+        let mut unrecognised = vec![];
+        for (position, left) in (0..frame_width).step_by(stride).enumerate() {
+            // Glyphs that end in empty columns can get cropped off on the right
+            let glyph: Vec<String> = rows.iter()
+                .map(|row| (left..left + self.width)
+                    .map(|x| if row.get(x) == Some(&'#') { '#' } else { '.' })
+                    .collect())
+                .collect();
+            match self.glyphs.get(&glyph) {
+                Some(letter) => result.push(*letter),
+                None => unrecognised.push((position, glyph.join("\n"))),
             }
         }
+
+        if unrecognised.is_empty() { Ok(result) } else { Err(OcrError::Unrecognised(unrecognised)) }
     }
-}
\ No newline at end of file
+}
+
+const AOC_GLYPHS: [(char, &str); 15] = [
+    ('A', "..##..\n.#..#.\n#....#\n#....#\n#....#\n######\n#....#\n#....#\n#....#\n#....#"),
+    ('B', "#####.\n#....#\n#....#\n#....#\n#####.\n#....#\n#....#\n#....#\n#....#\n#####."),
+    ('C', ".####.\n#....#\n#.....\n#.....\n#.....\n#.....\n#.....\n#.....\n#....#\n.####."),
+    ('E', "######\n#.....\n#.....\n#.....\n#####.\n#.....\n#.....\n#.....\n#.....\n######"),
+    ('F', "######\n#.....\n#.....\n#.....\n#####.\n#.....\n#.....\n#.....\n#.....\n#....."),
+    ('G', ".####.\n#....#\n#.....\n#.....\n#.....\n#..###\n#....#\n#....#\n#...##\n.###.#"),
+    ('H', "#....#\n#....#\n#....#\n#....#\n######\n#....#\n#....#\n#....#\n#....#\n#....#"),
+    ('J', "...###\n....#.\n....#.\n....#.\n....#.\n....#.\n....#.\n#...#.\n#...#.\n.###.."),
+    ('K', "#....#\n#...#.\n#..#..\n#.#...\n##....\n##....\n#.#...\n#..#..\n#...#.\n#....#"),
+    ('L', "#.....\n#.....\n#.....\n#.....\n#.....\n#.....\n#.....\n#.....\n#.....\n######"),
+    ('N', "#....#\n##...#\n##...#\n#.#..#\n#.#..#\n#..#.#\n#..#.#\n#...##\n#...##\n#....#"),
+    ('P', "#####.\n#....#\n#....#\n#....#\n#####.\n#.....\n#.....\n#.....\n#.....\n#....."),
+    ('R', "#####.\n#....#\n#....#\n#....#\n#####.\n#..#..\n#...#.\n#...#.\n#....#\n#....#"),
+    ('X', "#....#\n#....#\n.#..#.\n.#..#.\n..##..\n..##..\n.#..#.\n.#..#.\n#....#\n#....#"),
+    ('Z', "######\n.....#\n.....#\n....#.\n...#..\n..#...\n.#....\n#.....\n#.....\n######"),
+];
+
+/// Part 1: find the message and read it
+pub fn read_message(text: &str) -> Result<String, OcrError> {
+    Sky::from_text(text).read_message(&GlyphTable::aoc())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = "position=< 9,  1> velocity=< 0,  2>
+position=< 7,  0> velocity=<-1,  0>
+position=< 3, -2> velocity=<-1,  1>
+position=< 6, 10> velocity=<-2, -1>
+position=< 2, -4> velocity=< 2,  2>
+position=<-6, 10> velocity=< 2, -2>
+position=< 1,  8> velocity=< 1, -1>
+position=< 1,  7> velocity=< 1,  0>
+position=<-3, 11> velocity=< 1, -2>
+position=< 7,  6> velocity=<-1, -1>
+position=<-2,  3> velocity=< 1,  0>
+position=<-4,  3> velocity=< 2,  0>
+position=<10, -3> velocity=<-1,  1>
+position=< 5, 11> velocity=< 1, -2>
+position=< 4,  7> velocity=< 0, -1>
+position=< 8, -2> velocity=< 0,  1>
+position=<15,  0> velocity=<-2,  0>
+position=< 1,  6> velocity=< 1,  0>
+position=< 8,  9> velocity=< 0, -1>
+position=< 3,  3> velocity=<-1,  1>
+position=< 0,  5> velocity=< 0, -1>
+position=<-2,  2> velocity=< 2,  0>
+position=< 5, -2> velocity=< 1,  2>
+position=< 1,  4> velocity=< 2,  1>
+position=<-2,  7> velocity=< 2, -2>
+position=< 3,  6> velocity=<-1, -1>
+position=< 5,  0> velocity=< 1,  0>
+position=<-6,  0> velocity=< 2,  0>
+position=< 5,  9> velocity=< 1, -2>
+position=<14,  7> velocity=<-2,  0>
+position=<-3,  6> velocity=< 2, -1>";
+
+    const HI: &str = "#...#..###
+#...#...#.
+#...#...#.
+#####...#.
+#...#...#.
+#...#...#.
+#...#...#.
+#...#..###
+";
+
+    #[test]
+    fn test_message() {
+        let sky = Sky::from_text(EXAMPLE);
+        assert_eq!((3, HI.to_string()), sky.message());
+    }
+
+    #[test]
+    fn test_backwards_in_time() {
+        // Start the clock 10 seconds after the message
+        let mut sky = Sky::from_text(EXAMPLE);
+        for _t in 0..13 {
+            sky.update();
+        }
+        assert_eq!((-10, HI.to_string()), sky.message());
+        assert_eq!(Sky::from_text(EXAMPLE).positions_at(0), sky.positions_at(-13));
+    }
+
+    #[test]
+    fn test_aoc_font() {
+        let frame = "#........####...#####....####...#####...#....#
+#.......#....#..#....#..#....#..#....#..#....#
+#.......#.......#....#..#.......#....#...#..#.
+#.......#.......#....#..#.......#....#...#..#.
+#.......#.......#####...#.......#####.....##..
+#.......#.......#.......#..###..#.........##..
+#.......#.......#.......#....#..#........#..#.
+#.......#.......#.......#....#..#........#..#.
+#.......#....#..#.......#...##..#.......#....#
+######...####...#........###.#..#.......#....#";
+        assert_eq!(Ok("LCPGPX".to_string()), GlyphTable::aoc().recognize(frame));
+    }
+
+    #[test]
+    fn test_custom_glyphs() {
+        let mut glyphs = GlyphTable::new(5, 8, 2);
+        glyphs.insert('H', "#...#\n#...#\n#...#\n#####\n#...#\n#...#\n#...#\n#...#");
+        glyphs.insert('I', "###..\n.#...\n.#...\n.#...\n.#...\n.#...\n.#...\n###..");
+        assert_eq!(Ok("HI".to_string()), Sky::from_text(EXAMPLE).read_message(&glyphs));
+    }
+
+    #[test]
+    fn test_unrecognised_glyphs() {
+        let glyphs = GlyphTable::aoc();
+        assert_eq!(Err(OcrError::WrongHeight { expected: 10, found: 8 }), glyphs.recognize(HI));
+
+        // An L with a short foot
+        let frame = "#.....\n".repeat(9) + "#####.";
+        assert_eq!(Err(OcrError::Unrecognised(vec![(0, frame.clone())])), glyphs.recognize(&frame));
+    }
+
+    #[test]
+    fn test_render_options() {
+        let sky = Sky::from_text("position=< 0, 0> velocity=< 0, 0>
+position=< 2, 1> velocity=< 0, 0>");
+        let options = RenderOptions { star: '*', empty: ' ', margin: 1 };
+        assert_eq!("     \n *   \n   * \n     \n", sky.render_with(0, &options));
+    }
+
+    #[test]
+    fn test_pbm_export() {
+        let sky = Sky::from_text("position=< 0, 0> velocity=< 1, 0>
+position=< 2, 1> velocity=< 0, 0>");
+        let mut image = vec![];
+        sky.write_pbm(&mut image, 0, 0).unwrap();
+        assert_eq!("P1\n3 2\n1 0 0\n0 0 1\n", String::from_utf8(image).unwrap());
+
+        let directory = std::env::temp_dir().join(format!("day10-frames-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let paths = sky.export_frames(&directory, -1..=1, 0).unwrap();
+        assert_eq!(3, paths.len());
+        assert!(paths[0].ends_with("frame_-1.pbm"));
+        assert_eq!("P1\n2 2\n1 0\n0 1\n", std::fs::read_to_string(&paths[2]).unwrap());
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+}
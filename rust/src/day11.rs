@@ -1,30 +1,85 @@
 /// Day 11: Chronal Charge
 /// 
 /// Figure out the power contained in power cells
+/// 
+/// Cells are found by their (x, y) coordinate, counting from 1.  X goes
+/// left to right across the grid and Y goes top to bottom, so "top
+/// left" means smallest x and smallest y.
+
+use std::io::{self, Write};
+use std::ops::RangeInclusive;
+use std::thread;
+
+/// Where a cell is on the grid.  Both x and y start at 1.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Coordinate {
+    pub x: usize,
+    pub y: usize,
+}
+
+impl Coordinate {
+    pub fn new(x: usize, y: usize) -> Self {
+        Self { x, y }
+    }
+}
 
 /// A Grid of powercells with variable power levels
+/// 
+/// cells is stored a row at a time: cells[y - 1][x - 1] is the cell at
+/// (x, y)
+/// sums is a summed-area table: sums[row][col] is the total of every cell
+/// in cells[..row][..col], so any rectangle's total is four lookups away
 pub struct Grid {
-    cells: [[i32; 300]; 300],
+    cells: Vec<Vec<i32>>,
+    sums: Vec<Vec<i32>>,
     serial: isize,
+    width: usize,
+    height: usize,
 }
 
 impl Grid {
+    /// The puzzle's 300x300 grid
     pub fn new(serial: isize) -> Self {
-        let mut grid = Self { serial, cells: [[0; 300]; 300] };
+        Grid::with_size(serial, 300, 300)
+    }
+
+    /// A grid that's `width` cells across (x) and `height` cells down (y)
+    pub fn with_size(serial: isize, width: usize, height: usize) -> Self {
+        let mut grid = Self { serial, cells: vec![vec![0; width]; height], sums: vec![], width, height };
         grid.generate_values();
+        grid.generate_sums();
         grid
     }
 
     /// Each cell has a location/serial number-based checksum that
     /// determines its power level.  Calculates all cells.
     fn generate_values(&mut self) {
-        for (i, row) in self.cells.iter_mut().enumerate() {
-            for (j, value) in row.iter_mut().enumerate() {
-                *value = Grid::power_level(self.serial, (i + 1) as i32, (j + 1) as i32);
+        for (row, cells) in self.cells.iter_mut().enumerate() {
+            for (col, value) in cells.iter_mut().enumerate() {
+                *value = Grid::power_level(self.serial, (col + 1) as i32, (row + 1) as i32);
+            }
+        }
+    }
+
+    /// Builds the summed-area table from the cells
+    fn generate_sums(&mut self) {
+        self.sums = vec![vec![0; self.width + 1]; self.height + 1];
+        for row in 0..self.height {
+            for col in 0..self.width {
+                self.sums[row + 1][col + 1] = self.cells[row][col] + self.sums[row][col + 1]
+                    + self.sums[row + 1][col] - self.sums[row][col];
             }
         }
     }
 
+    /// Total power in the width x height rectangle whose top left cell
+    /// is at `corner`
+    fn rectangle_total(&self, corner: Coordinate, width: usize, height: usize) -> i32 {
+        let (row, col) = (corner.y - 1, corner.x - 1);
+        self.sums[row + height][col + width] - self.sums[row][col + width]
+            - self.sums[row + height][col] + self.sums[row][col]
+    }
+
     /// The checksum power level calculation on a cell basis.
     /// 
     /// Depends on the grid's serial number and on the location of the cell
@@ -35,48 +90,127 @@ impl Grid {
         (result - 5) as i32
     }
 
+    /// The power level of the cell at (x, y), or None if that's off the grid
+    pub fn power_at(&self, x: usize, y: usize) -> Option<i32> {
+        if x == 0 || y == 0 {
+            return None;
+        }
+        self.cells.get(y - 1)?.get(x - 1).cloned()
+    }
+
     /// Finds the cell that is at the top left of the 3x3 with the highest
-    /// total in the grid.
-    pub fn best_cell(&self) -> (usize, usize) {
-        let mut max_value = 0;
-        let mut max_location = (0, 0);
-        for i in 0..298 {
-            for j in 0..298 {
-                let value = self.cells.iter()
-                    .skip(i).take(3)
-                    .flat_map(|row| row.iter().skip(j).take(3) )
-                    .sum();
-                if value > max_value {
-                    max_value = value;
-                    max_location = (i + 1, j + 1);
-                }
+    /// total in the grid, or None if the grid is smaller than 3x3
+    pub fn best_cell(&self) -> Option<Coordinate> {
+        self.best_rectangle(3, 3).map(|(corner, _power)| corner)
+    }
+
+    /// Finds the width x height rectangle with the highest total,
+    /// returning its top left cell and its total, or None if it doesn't
+    /// fit in the grid
+    /// 
+    /// Ties go to the cell with the smallest x, then the smallest y.
+    pub fn best_rectangle(&self, width: usize, height: usize) -> Option<(Coordinate, i32)> {
+        self.top_rectangles(width, height, 1).pop()
+    }
+
+    /// The `k` width x height rectangles with the highest totals, best
+    /// first, as (top left cell, total)
+    pub fn top_rectangles(&self, width: usize, height: usize, k: usize) -> Vec<(Coordinate, i32)> {
+        let mut windows = self.windows(width, height);
+        windows.sort_by_key(|&(corner, power)| (-power, corner));
+        windows.truncate(k);
+        windows
+    }
+
+    /// Every width x height rectangle that fits, as (top left cell, total)
+    fn windows(&self, width: usize, height: usize) -> Vec<(Coordinate, i32)> {
+        if width == 0 || height == 0 || width > self.width || height > self.height {
+            return vec![];
+        }
+        let mut windows = Vec::with_capacity((self.width - width + 1) * (self.height - height + 1));
+        for x in 1..=(self.width - width + 1) {
+            for y in 1..=(self.height - height + 1) {
+                let corner = Coordinate::new(x, y);
+                windows.push((corner, self.rectangle_total(corner, width, height)));
             }
         }
-        max_location
+        windows
     }
 
     /// Finds the cell that is at the top left of the N x N grid with
-    /// the highest total power level, where N can be any size between
-    /// 1 and 300.
-    pub fn best_cell_sized(&self) -> (usize, usize, usize) {
-        let mut max_value = 0;
-        let mut max_location = (0, 0, 0);
-        for size in 1..=30 {
-            println!("Doing iteration: {}", size);
-            for i in 0..=(300 - size) {
-                for j in 0..=(300 - size) {
-                    let value = self.cells.iter()
-                        .skip(i).take(size)
-                        .flat_map(|row| row.iter().skip(j).take(size) )
-                        .sum();
-                    if value > max_value {
-                        max_value = value;
-                        max_location = (i + 1, j + 1, size);
+    /// the highest total power level, where N can be any size that fits
+    /// (1 to 300 for the puzzle's grid).  Returns the cell and N, or
+    /// None for an empty grid.
+    pub fn best_cell_sized(&self) -> Option<(Coordinate, usize)> {
+        self.best_square(1..=self.width.min(self.height))
+            .map(|(corner, size, _power)| (corner, size))
+    }
+
+    /// Finds the best square out of all of the sizes given, returning
+    /// its top left cell, its size, and its total power
+    /// 
+    /// Sizes that don't fit in the grid are skipped, and if none of
+    /// them fit, there's no best square.  Ties go to the smallest size,
+    /// then the smallest x, then the smallest y.
+    pub fn best_square(&self, sizes: RangeInclusive<usize>) -> Option<(Coordinate, usize, i32)> {
+        let mut best: Option<(Coordinate, usize, i32)> = None;
+        let largest = self.width.min(self.height);
+        for size in sizes.filter(|size| (1..=largest).contains(size)) {
+            for x in 1..=(self.width - size + 1) {
+                for y in 1..=(self.height - size + 1) {
+                    let corner = Coordinate::new(x, y);
+                    let value = self.rectangle_total(corner, size, size);
+                    if best.is_none_or(|(_corner, _size, power)| value > power) {
+                        best = Some((corner, size, value));
                     }
                 }
             }
         }
-        max_location
+        best
+    }
+
+    /// Same as `best_square`, but splits the sizes up between `threads`
+    /// threads
+    pub fn best_square_parallel(&self, sizes: RangeInclusive<usize>, threads: usize) -> Option<(Coordinate, usize, i32)> {
+        let threads = threads.max(1);
+        let sizes: Vec<usize> = sizes.collect();
+        thread::scope(|scope| {
+            // Deal the sizes out like cards since small sizes take longer
+            let workers: Vec<_> = (0..threads).map(|k| {
+                let mine: Vec<usize> = sizes.iter().cloned().skip(k).step_by(threads).collect();
+                scope.spawn(move || {
+                    mine.into_iter()
+                        .filter_map(|size| self.best_square(size..=size))
+                        .collect::<Vec<_>>()
+                })
+            }).collect();
+            workers.into_iter()
+                .flat_map(|worker| worker.join().unwrap())
+                .min_by_key(|&(corner, size, power)| (-power, size, corner))
+        })
+    }
+
+    /// Lays out every power level as CSV, one line per y (top to
+    /// bottom) and one column per x (left to right)
+    pub fn to_csv(&self) -> String {
+        let mut result = String::new();
+        for row in self.cells.iter() {
+            let values: Vec<String> = row.iter().map(|value| value.to_string()).collect();
+            result.push_str(&values.join(","));
+            result.push('\n');
+        }
+        result
+    }
+
+    /// Draws the power levels as a plain (P2) PGM image, laid out like
+    /// `to_csv`.  Power levels run from -5 (black) to 4 (white).
+    pub fn write_pgm<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        writeln!(out, "P2\n{} {}\n9", self.width, self.height)?;
+        for row in self.cells.iter() {
+            let values: Vec<String> = row.iter().map(|value| (value + 5).to_string()).collect();
+            writeln!(out, "{}", values.join(" "))?;
+        }
+        Ok(())
     }
 }
 
@@ -87,13 +221,13 @@ mod tests {
     #[test]
     fn test_part_one() {
         let grid = Grid::new(18);
-        assert_eq!(grid.best_cell(), (33, 45));
+        assert_eq!(grid.best_cell(), Some(Coordinate::new(33, 45)));
     }
 
     #[test]
     fn test_part_one_2() {
         let grid = Grid::new(42);
-        assert_eq!(grid.best_cell(), (21, 61));
+        assert_eq!(grid.best_cell(), Some(Coordinate::new(21, 61)));
     }
 
     #[test]
@@ -114,13 +248,76 @@ mod tests {
     #[test]
     fn test_part_two_1() {
         let grid = Grid::new(18);
-        assert_eq!(grid.best_cell_sized(), (90, 269, 16));
+        assert_eq!(grid.best_cell_sized(), Some((Coordinate::new(90, 269), 16)));
     }
 
     #[test]
     fn test_part_two_2() {
         let grid = Grid::new(42);
-        assert_eq!(grid.best_cell_sized(), (232, 251, 12));
+        assert_eq!(grid.best_cell_sized(), Some((Coordinate::new(232, 251), 12)));
+    }
+
+    #[test]
+    fn test_best_square_power() {
+        let grid = Grid::new(18);
+        assert_eq!(Some((Coordinate::new(90, 269), 16, 113)), grid.best_square(1..=300));
+        assert_eq!(Some((Coordinate::new(33, 45), 3, 29)), grid.best_square(3..=3));
+    }
+
+    #[test]
+    fn test_best_square_parallel() {
+        let grid = Grid::new(42);
+        assert_eq!(Some((Coordinate::new(232, 251), 12, 119)), grid.best_square_parallel(1..=300, 4));
+        assert_eq!(grid.best_square(1..=20), grid.best_square_parallel(1..=20, 3));
+    }
+
+    #[test]
+    fn test_rectangles() {
+        let grid = Grid::new(18);
+        assert_eq!(Some((Coordinate::new(33, 45), 29)), grid.best_rectangle(3, 3));
+        assert_eq!(grid.top_rectangles(3, 3, 1), vec![(Coordinate::new(33, 45), 29)]);
+        let top = grid.top_rectangles(5, 2, 10);
+        assert_eq!(10, top.len());
+        assert!(top.windows(2).all(|pair| pair[0].1 >= pair[1].1));
+        assert_eq!(None, grid.best_rectangle(301, 1));
+    }
+
+    #[test]
+    fn test_all_negative() {
+        // Every cell in this little grid is negative
+        let grid = Grid::with_size(0, 3, 2);
+        assert_eq!(Some((Coordinate::new(3, 2), -2)), grid.best_rectangle(1, 1));
+        assert_eq!(vec![(Coordinate::new(3, 2), -2), (Coordinate::new(1, 2), -3), (Coordinate::new(2, 2), -3)],
+            grid.top_rectangles(1, 1, 3));
+        assert_eq!(Some((Coordinate::new(2, 1), -13)), grid.best_rectangle(2, 2));
+        assert_eq!(Some((Coordinate::new(3, 2), 1, -2)), grid.best_square(1..=300));
+    }
+
+    #[test]
+    fn test_too_small() {
+        let grid = Grid::with_size(18, 2, 2);
+        assert_eq!(None, grid.best_cell());
+        assert_eq!(None, grid.best_square(3..=300));
+        assert_eq!(None, grid.best_square_parallel(3..=300, 2));
+        assert!(grid.best_cell_sized().is_some_and(|(_corner, size)| size <= 2));
+        assert_eq!(None, Grid::with_size(18, 0, 0).best_cell_sized());
+    }
+
+    #[test]
+    fn test_power_at() {
+        let grid = Grid::new(57);
+        assert_eq!(Some(-5), grid.power_at(122, 79));
+        assert_eq!(None, grid.power_at(0, 1));
+        assert_eq!(None, grid.power_at(1, 301));
     }
 
-}
\ No newline at end of file
+    #[test]
+    fn test_exports() {
+        let grid = Grid::with_size(0, 3, 2);
+        assert_eq!("-4,-4,-4\n-3,-3,-2\n", grid.to_csv());
+
+        let mut image = vec![];
+        grid.write_pgm(&mut image).unwrap();
+        assert_eq!("P2\n3 2\n9\n1 1 1\n2 2 3\n", String::from_utf8(image).unwrap());
+    }
+}
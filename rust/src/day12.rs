@@ -3,55 +3,492 @@
 /// Figure out which plants are growing based on the plants around them
 
 use std::collections::HashMap;
+use std::io::{self, Write};
+use std::ops::RangeInclusive;
 
+/// A repeating pattern: from generation `start` on, every `period`
+/// generations the plants come back to the same shape, moved `shift`
+/// pots to the right (or left, if negative)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Cycle {
+    pub start: usize,
+    pub period: usize,
+    pub shift: isize,
+}
+
+/// A summary of one generation: where its first plant is, the pot
+/// number total, and how many plants there are
+struct Snapshot {
+    first: isize,
+    total: isize,
+    count: isize,
+}
+
+/// The most neighborhoods a rule table can cover, to keep it a
+/// reasonable size
+const MAX_RULES: usize = 1 << 24;
+
+/// Ways that plant rules and puzzle input can fail to make sense
+/// 
+/// Lines are counted from 1, through the rules for `with_alphabet` and
+/// through the whole puzzle for `from_puzzle`.
+#[derive(Debug, PartialEq)]
+pub enum ParseError {
+    /// The puzzle doesn't start with "initial state: ..."
+    MissingHeader,
+    /// The alphabet is empty, has more than 256 states, or has the same
+    /// state twice
+    BadAlphabet,
+    /// Something in the initial state that isn't one of the states.
+    /// `position` counts characters from 0.
+    BadInitialState { position: usize, state: char },
+    /// A line that isn't "pattern => state"
+    BadRule { line: usize, text: String },
+    /// A rule using something that isn't one of the states
+    UnknownState { line: usize, state: char },
+    /// The first rule's pattern has no middle pot
+    EvenWidth { line: usize, width: usize },
+    /// A rule whose pattern isn't as wide as the first rule's
+    WrongWidth { line: usize, expected: usize, found: usize },
+    /// Patterns this wide have too many possible neighborhoods to keep
+    /// a rule for each
+    TooManyNeighborhoods { width: usize },
+    /// A rule that grows something out of an all-empty neighborhood
+    GrowsFromNothing { line: usize },
+}
+
+/// One generation's row of pots, trimmed down to the stretch from the
+/// first non-empty pot to the last
+#[derive(Debug, Clone, PartialEq)]
+pub struct Generation {
+    pub generation: usize,
+    pub first: isize,
+    pub states: Vec<char>,
+    pub live_count: isize,
+}
+
+/// A run of generations, for seeing how the plants change over time
+/// 
+/// Rows are lined up by pot number, so a plant that stays put is a
+/// vertical line and a glider is a diagonal one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct History {
+    pub generations: Vec<Generation>,
+    empty: char,
+}
+
+impl History {
+    /// The lowest and highest pot numbers that any generation uses.
+    /// Pot 0 is always included so that it can be marked.
+    fn pot_range(&self) -> (isize, isize) {
+        self.generations.iter()
+            .filter(|row| !row.states.is_empty())
+            .fold((0, 0), |(low, high), row| {
+                (low.min(row.first), high.max(row.first + row.states.len() as isize - 1))
+            })
+    }
+
+    /// What's in pot number `pot` in one of the rows
+    fn state_at(&self, row: &Generation, pot: isize) -> char {
+        if pot < row.first {
+            return self.empty;
+        }
+        row.states.get((pot - row.first) as usize).cloned().unwrap_or(self.empty)
+    }
+
+    /// Draws a space-time diagram: one line per generation, labelled
+    /// with the generation number, with time running down the page
+    /// 
+    /// The first line has a 'v' over pot 0, and empty pots in that
+    /// column are drawn as '|' so it's easy to follow down the page.
+    pub fn render(&self) -> String {
+        let (low, high) = self.pot_range();
+        let label_width = self.generations.last().map_or(1, |row| row.generation.to_string().len());
+        let mut result = format!("{:width$} {}v\n", "", " ".repeat((0 - low) as usize), width = label_width);
+        for row in self.generations.iter() {
+            let line: String = (low..=high)
+                .map(|pot| match self.state_at(row, pot) {
+                    state if pot == 0 && state == self.empty => '|',
+                    state => state,
+                })
+                .collect();
+            result.push_str(&format!("{:>width$} {}\n", row.generation, line, width = label_width));
+        }
+        result
+    }
+
+    /// Draws the same diagram as `render` as a plain (P1) PBM image,
+    /// with non-empty pots in black
+    /// 
+    /// The top row of pixels is a marker, black only in the pot 0
+    /// column, and the generations follow below it.
+    pub fn write_pbm<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        let (low, high) = self.pot_range();
+        writeln!(out, "P1\n{} {}", high - low + 1, self.generations.len() + 1)?;
+        let marker: Vec<&str> = (low..=high).map(|pot| if pot == 0 { "1" } else { "0" }).collect();
+        writeln!(out, "{}", marker.join(" "))?;
+        for row in self.generations.iter() {
+            let line: Vec<&str> = (low..=high)
+                .map(|pot| if self.state_at(row, pot) == self.empty { "0" } else { "1" })
+                .collect();
+            writeln!(out, "{}", line.join(" "))?;
+        }
+        Ok(())
+    }
+
+    /// Each generation's number along with its `live_count`
+    pub fn live_counts(&self) -> Vec<(usize, isize)> {
+        self.generations.iter().map(|row| (row.generation, row.live_count)).collect()
+    }
+
+    /// Lists `live_counts` as "generation,live_count" rows
+    pub fn to_csv(&self) -> String {
+        let mut result = String::from("generation,live_count\n");
+        for (generation, live_count) in self.live_counts() {
+            result.push_str(&format!("{},{}\n", generation, live_count));
+        }
+        result
+    }
+}
+
+/// A row of pots, some with plants in them, and the rules for how
+/// they grow
+/// 
+/// This works for any one-dimensional cellular automaton where each
+/// pot looks at `radius` pots on either side, and pots can be in any
+/// of the states in `alphabet`.  The first state in the alphabet means
+/// "empty" ('.' for the puzzle).
+/// 
+/// pots is packed with `bits` bits per pot, holding the index of the
+/// pot's state in the alphabet.  len is how many pots it covers, and
+/// zero is which of those is pot 0.  Only the stretch from the first
+/// plant to the last is kept (with a little slack), so the row never
+/// grows past the plants.
+/// rules has an entry for every possible neighborhood.  The index is
+/// the neighborhood's states read left to right as the digits of a
+/// number in base alphabet.len(), so for the puzzle there are 32.
+#[derive(Clone)]
 pub struct Plants {
-    pub plants: Vec<char>,
-    pub rules: HashMap<Vec<char>, char>,
+    pots: Vec<u64>,
+    len: usize,
+    bits: usize,
+    radius: usize,
+    alphabet: Vec<char>,
+    rules: Vec<u8>,
     pub zero: isize
 }
 
 impl Plants {
-    pub fn new(initial: &str, rules: &str) -> Self {
-        let mut plants = Self { 
-            plants: initial.chars().collect(),
-            rules: HashMap::new(),
-            zero: 0,
+    /// Loads plants with the puzzle's '.' (empty) and '#' (plant) states
+    /// 
+    /// The neighborhood size comes from the rules.
+    pub fn new(initial: &str, rules: &str) -> Result<Self, ParseError> {
+        Plants::with_alphabet(initial, rules, ".#")
+    }
+
+    /// Loads plants with any set of states.  The first state in
+    /// `alphabet` means empty.
+    /// 
+    /// Rules look like "..#.. => #", and every rule has to have the
+    /// same odd number of pots on the left.  Neighborhoods without a
+    /// rule come out empty.
+    /// 
+    /// The row goes on forever both ways, so a rule that grows plants
+    /// out of an all-empty neighborhood would fill it up; those are
+    /// errors too.
+    pub fn with_alphabet(initial: &str, rules: &str, alphabet: &str) -> Result<Self, ParseError> {
+        Plants::parse(initial, rules.lines().enumerate().map(|(index, line)| (index + 1, line)), alphabet)
+    }
+
+    /// Loads puzzle input: an "initial state: ..." line, a blank line,
+    /// and then the rules
+    pub fn from_puzzle(text: &str) -> Result<Self, ParseError> {
+        let mut lines = text.lines().enumerate().map(|(index, line)| (index + 1, line));
+        let initial = lines.next()
+            .and_then(|(_line, header)| header.trim().strip_prefix("initial state:"))
+            .ok_or(ParseError::MissingHeader)?;
+        Plants::parse(initial.trim(), lines, ".#")
+    }
+
+    /// Builds plants from the initial state and numbered rule lines
+    fn parse<'a, I>(initial: &str, lines: I, alphabet: &str) -> Result<Self, ParseError>
+        where I: Iterator<Item = (usize, &'a str)>
+    {
+        let alphabet: Vec<char> = alphabet.chars().collect();
+        let mut sorted = alphabet.clone();
+        sorted.sort();
+        sorted.dedup();
+        if alphabet.is_empty() || alphabet.len() > 256 || sorted.len() != alphabet.len() {
+            return Err(ParseError::BadAlphabet);
+        }
+
+        let mut rules: Vec<(usize, Vec<char>, char)> = vec![];
+        for (line, text) in lines.filter(|(_line, text)| !text.trim().is_empty()) {
+            let mut parts = text.trim().split(" => ");
+            let bad_rule = || ParseError::BadRule { line, text: text.to_string() };
+            let input: Vec<char> = parts.next().ok_or_else(bad_rule)?.chars().collect();
+            let output: Vec<char> = parts.next().ok_or_else(bad_rule)?.chars().collect();
+            if input.is_empty() || output.len() != 1 || parts.next().is_some() {
+                return Err(bad_rule());
+            }
+            rules.push((line, input, output[0]));
+        }
+
+        let width = match rules.first() {
+            Some((line, input, _output)) if input.len() % 2 == 0 => {
+                return Err(ParseError::EvenWidth { line: *line, width: input.len() });
+            }
+            Some((_line, input, _output)) => input.len(),
+            None => 5,
         };
-        for line in rules.lines() {
-            let mut parts = line.split(" => ");
-            let input = parts.next().unwrap();
-            let output = parts.next().unwrap();
-            plants.rules.insert(input.chars().collect(), output.chars().next().unwrap());
+        let mut plants = Plants::empty(alphabet, width / 2)
+            .ok_or(ParseError::TooManyNeighborhoods { width })?;
+        for (line, input, output) in rules {
+            if input.len() != width {
+                return Err(ParseError::WrongWidth { line, expected: width, found: input.len() });
+            }
+            let unknown = |state: char| ParseError::UnknownState { line, state };
+            let mut index = 0;
+            for c in input {
+                index = index * plants.alphabet.len() + plants.state_of(c).ok_or_else(|| unknown(c))?;
+            }
+            plants.rules[index] = plants.state_of(output).ok_or_else(|| unknown(output))? as u8;
+            if index == 0 && plants.rules[0] != 0 {
+                return Err(ParseError::GrowsFromNothing { line });
+            }
         }
+
+        let mut states: Vec<u8> = vec![];
+        for (position, c) in initial.trim().chars().enumerate() {
+            let state = plants.state_of(c).ok_or(ParseError::BadInitialState { position, state: c })?;
+            states.push(state as u8);
+        }
+        plants.set_pots(&states);
+        Ok(plants)
+    }
+
+    /// An elementary-style automaton with plants ('#') and empty pots
+    /// ('.') using Wolfram's numbering: bit n of `rule` is what a pot
+    /// becomes when its neighborhood, read as binary, is n
+    /// 
+    /// Rule 90 with radius 1 is the Sierpinski triangle, for example.
+    /// Odd rules (with bit 0 set) would fill the endless row with
+    /// plants, so they panic.
+    pub fn wolfram(initial: &str, radius: usize, rule: u128) -> Self {
+        assert!(radius <= 3, "Rule numbers only go up to radius 3");
+        assert!(rule & 1 == 0, "Odd rules turn empty neighborhoods into plants");
+        let mut plants = Plants::empty(vec!['.', '#'], radius).unwrap();
+        for (index, result) in plants.rules.iter_mut().enumerate() {
+            *result = ((rule >> index) & 1) as u8;
+        }
+        let states: Vec<u8> = initial.trim().chars()
+            .map(|c| plants.state_of(c).expect("Wolfram rows only have '.' and '#'") as u8)
+            .collect();
+        plants.set_pots(&states);
         plants
     }
 
+    /// A row with no pots that looks `radius` pots each way and has
+    /// no rules yet, or None if there'd be too many neighborhoods
+    /// 
+    /// The alphabet has to have between 1 and 256 states.
+    fn empty(alphabet: Vec<char>, radius: usize) -> Option<Self> {
+        // Pots can't be split across words, so stick to sizes that divide 64
+        let bits = [1, 2, 4, 8].iter().cloned()
+            .find(|bits| alphabet.len() <= 1 << bits)
+            .unwrap();
+        let rule_count = (2*radius as u32).checked_add(1)
+            .and_then(|width| alphabet.len().checked_pow(width))
+            .filter(|&count| count <= MAX_RULES)?;
+        let rules = vec![0; rule_count];
+        Some(Self { pots: vec![], len: 0, bits, radius, alphabet, rules, zero: 0 })
+    }
+
+    /// Which state in the alphabet a character stands for
+    fn state_of(&self, c: char) -> Option<usize> {
+        self.alphabet.iter().position(|state| *state == c)
+    }
+
+    /// Replaces the row with new pots, counting from the current pot 0
+    fn set_pots(&mut self, states: &[u8]) {
+        let per_word = 64 / self.bits;
+        self.pots = vec![0; states.len().div_ceil(per_word)];
+        self.len = states.len();
+        for (index, state) in states.iter().enumerate() {
+            self.pots[index / per_word] |= (*state as u64) << (self.bits * (index % per_word));
+        }
+    }
+
+    /// The state of the pot at this spot in the row.  Spots off either
+    /// end are empty.
+    fn get(&self, index: isize) -> usize {
+        if index < 0 || index as usize >= self.len {
+            return 0;
+        }
+        let index = index as usize;
+        let per_word = 64 / self.bits;
+        let mask = (1u64 << self.bits) - 1;
+        ((self.pots[index / per_word] >> (self.bits * (index % per_word))) & mask) as usize
+    }
+
+    /// What's in pot number `pot`
+    pub fn state_at(&self, pot: isize) -> char {
+        self.alphabet[self.get(pot + self.zero)]
+    }
+
+    /// Whether pot number `pot` has anything in it
+    pub fn is_alive(&self, pot: isize) -> bool {
+        self.get(pot + self.zero) != 0
+    }
+
+    /// The numbers of every pot that isn't empty, left to right
+    pub fn live_pots(&self) -> Vec<isize> {
+        (0..self.len as isize)
+            .filter(|&index| self.get(index) != 0)
+            .map(|index| index - self.zero)
+            .collect()
+    }
+
     pub fn iterate(&mut self, times: usize) {
-        for t in 0..times {
-            if t % 1000000 == 0 { println!("{}", t); }
-            if self.plants.iter().take(5).any(|c| *c == '#') {
-                for _i in 0..5 { self.plants.insert(0, '.'); }
-                self.zero += 5;
+        for _t in 0..times {
+            self.step();
+        }
+    }
+
+    /// Grows the plants one generation
+    /// 
+    /// Slides a window along from `radius` pots before the first plant
+    /// to `radius` pots after the last, looking up each window in the
+    /// rules.  The constructors make sure empty neighborhoods stay
+    /// empty, so nothing further out can change.
+    pub fn step(&mut self) {
+        let (first, last) = match self.bounds() {
+            Some(bounds) => bounds,
+            None => return,
+        };
+        let radius = self.radius as isize;
+        let states = self.alphabet.len();
+        let window_count = self.rules.len();
+        let mut next: Vec<u8> = Vec::with_capacity((last - first + 1 + 2*radius) as usize);
+        let mut window = 0;
+        for index in (first - radius)..=(last + radius) {
+            window = (window * states + self.get(index + radius)) % window_count;
+            next.push(self.rules[window]);
+        }
+        self.zero -= first - radius;
+        self.set_pots(&next);
+    }
+
+    /// The positions of the first and last plants in the row, if there
+    /// are any
+    fn bounds(&self) -> Option<(isize, isize)> {
+        let per_word = 64 / self.bits;
+        let first_word = self.pots.iter().position(|word| *word != 0)?;
+        let last_word = self.pots.iter().rposition(|word| *word != 0)?;
+        let first = first_word * per_word + self.pots[first_word].trailing_zeros() as usize / self.bits;
+        let last = last_word * per_word + (63 - self.pots[last_word].leading_zeros() as usize) / self.bits;
+        Some((first as isize, last as isize))
+    }
+
+    /// Records every generation in `generations` (counting these
+    /// plants as generation 0) without changing these plants
+    pub fn history(&self, generations: RangeInclusive<usize>) -> History {
+        let mut plants = self.clone();
+        plants.iterate(*generations.start());
+        let mut rows = vec![];
+        for generation in generations {
+            if !rows.is_empty() {
+                plants.step();
+            }
+            let (first, states) = match plants.bounds() {
+                Some((first, last)) => (first - plants.zero, (first..=last).map(|index| plants.alphabet[plants.get(index)]).collect()),
+                None => (0, vec![]),
+            };
+            rows.push(Generation { generation, first, states, live_count: plants.live_count() });
+        }
+        History { generations: rows, empty: self.alphabet[0] }
+    }
+
+    /// What `live_count` would be after `generations` generations,
+    /// without changing these plants
+    /// 
+    /// Once the plants settle into a repeating pattern (sliding along
+    /// like a glider, or cycling through a few shapes), the answer for
+    /// any later generation can be worked out directly, so this is
+    /// fine for generation counts in the billions.
+    /// 
+    /// Gives up with None if it's run `max_generations` generations
+    /// without either getting there or finding a pattern.
+    pub fn live_count_after(&self, generations: u64, max_generations: usize) -> Option<isize> {
+        let mut plants = self.clone();
+        let mut history: Vec<Snapshot> = vec![];
+        let mut seen: HashMap<String, usize> = HashMap::new();
+        for generation in 0..=max_generations {
+            let (shape, snapshot) = plants.snapshot();
+            if generation as u64 == generations {
+                return Some(snapshot.total);
             }
-            if self.plants.iter().rev().take(5).any(|c| *c == '#') {
-                for _i in 0..5 { self.plants.push('.'); }
+            if let Some(&start) = seen.get(&shape) {
+                let cycle = Cycle { start, period: generation - start, shift: snapshot.first - history[start].first };
+                return Some(Plants::extrapolate(&history, cycle, generations));
             }
-            let mut new_plants = self.plants.clone();
-            for i in 2..(self.plants.len() - 2) {
-                let neighborhood = &self.plants[i - 2..=i+2];
-                new_plants[i] = *self.rules.get(neighborhood).unwrap_or(&'.');
+            seen.insert(shape, generation);
+            history.push(snapshot);
+            plants.step();
+        }
+        None
+    }
+
+    /// Runs up to `max_generations` generations looking for the point
+    /// where the plants start repeating themselves
+    pub fn find_cycle(&self, max_generations: usize) -> Option<Cycle> {
+        let mut plants = self.clone();
+        let mut firsts: Vec<isize> = vec![];
+        let mut seen: HashMap<String, usize> = HashMap::new();
+        for generation in 0..=max_generations {
+            let (shape, snapshot) = plants.snapshot();
+            if let Some(&start) = seen.get(&shape) {
+                return Some(Cycle { start, period: generation - start, shift: snapshot.first - firsts[start] });
             }
-            self.plants = new_plants;
+            seen.insert(shape, generation);
+            firsts.push(snapshot.first);
+            plants.step();
         }
+        None
+    }
+
+    /// Works out the total at a generation past the start of a cycle
+    /// from the generations already seen
+    /// 
+    /// Each trip around the cycle slides every plant over by `shift`.
+    fn extrapolate(history: &[Snapshot], cycle: Cycle, generations: u64) -> isize {
+        let since_start = generations - cycle.start as u64;
+        let laps = (since_start / cycle.period as u64) as isize;
+        let matching = &history[cycle.start + (since_start % cycle.period as u64) as usize];
+        matching.total + laps * cycle.shift * matching.count
     }
 
+    /// The plants with the empty pots trimmed off both ends, along with
+    /// a summary of where they are
+    fn snapshot(&self) -> (String, Snapshot) {
+        let bounds = self.bounds();
+        let shape: String = match bounds {
+            Some((first, last)) => (first..=last)
+                .map(|index| self.alphabet[self.get(index)])
+                .collect(),
+            None => String::new(),
+        };
+        let snapshot = Snapshot {
+            first: bounds.map_or(0, |(first, _last)| first - self.zero),
+            total: self.live_count(),
+            count: self.live_pots().len() as isize,
+        };
+        (shape, snapshot)
+    }
+
+    /// Adds up the numbers of every pot that isn't empty
     pub fn live_count(&self) -> isize {
-        self.plants.iter().enumerate()
-            .filter(|(_index, plant)| **plant == '#')
-            .map(|(index, _plant)|{
-                (index as isize) - self.zero
-            })
-            .sum()
+        self.live_pots().iter().sum()
     }
 }
 
@@ -74,8 +511,180 @@ mod tests {
 ##.## => #
 ###.. => #
 ###.# => #
-####. => #");
+####. => #").unwrap();
         plants.iterate(20);
         assert_eq!(325, plants.live_count());
     }
-}
\ No newline at end of file
+
+    const EXAMPLE_RULES: &str = "...## => #
+..#.. => #
+.#... => #
+.#.#. => #
+.#.## => #
+.##.. => #
+.#### => #
+#.#.# => #
+#.### => #
+##.#. => #
+##.## => #
+###.. => #
+###.# => #
+####. => #";
+
+    #[test]
+    fn test_glider_extrapolation() {
+        let plants = Plants::new("#..#.#..##......###...###", EXAMPLE_RULES).unwrap();
+        assert_eq!(Some(325), plants.live_count_after(20, 1000));
+        let cycle = plants.find_cycle(1000).unwrap();
+        assert_eq!(1, cycle.period);
+
+        let mut later = Plants::new("#..#.#..##......###...###", EXAMPLE_RULES).unwrap();
+        later.iterate(300);
+        assert_eq!(Some(later.live_count()), plants.live_count_after(300, 1000));
+
+        // Past the start of the glide, every generation adds shift * count
+        let count = later.live_pots().len() as isize;
+        let expected = later.live_count() + (50_000_000_000 - 300) * cycle.shift * count;
+        assert_eq!(Some(expected), plants.live_count_after(50_000_000_000, 1000));
+    }
+
+    #[test]
+    fn test_oscillator() {
+        let rules = "...#. => #
+...## => #
+..#.. => #
+.##.. => #
+.##.# => #";
+        let plants = Plants::new("##", rules).unwrap();
+        assert_eq!(Some(Cycle { start: 0, period: 3, shift: -3 }), plants.find_cycle(10));
+
+        let mut stepped = Plants::new("##", rules).unwrap();
+        for generation in 0..20 {
+            assert_eq!(Some(stepped.live_count()), plants.live_count_after(generation, 10));
+            stepped.step();
+        }
+    }
+
+    #[test]
+    fn test_row_stays_trimmed() {
+        let mut plants = Plants::new("#..#.#..##......###...###", EXAMPLE_RULES).unwrap();
+        plants.iterate(20);
+        assert_eq!(vec![-2, 3, 4, 9, 10, 11, 12, 13, 17, 18, 19, 20, 21, 22, 23, 28, 30, 33, 34],
+            plants.live_pots());
+        assert!(plants.is_alive(-2));
+        assert!(!plants.is_alive(-1));
+
+        plants.iterate(10_000);
+        assert!(plants.len < 200);
+    }
+
+    #[test]
+    fn test_from_puzzle() {
+        let puzzle = format!("initial state: #..#.#..##......###...###\n\n{}\n", EXAMPLE_RULES);
+        let mut plants = Plants::from_puzzle(&puzzle).unwrap();
+        plants.iterate(20);
+        assert_eq!(325, plants.live_count());
+    }
+
+    #[test]
+    fn test_wolfram_rule_90() {
+        let mut plants = Plants::wolfram("#", 1, 90);
+        plants.iterate(3);
+        assert_eq!(vec![-3, -1, 1, 3], plants.live_pots());
+        plants.step();
+        assert_eq!(vec![-4, 4], plants.live_pots());
+    }
+
+    #[test]
+    fn test_radius_and_alphabet() {
+        // Radius 1, three states: a seed turns into a sprout, which
+        // spreads seeds to either side
+        let rules = ".s. => o
+.o. => .
+o.. => s
+..o => s
+s.. => .
+..s => .";
+        let mut plants = Plants::with_alphabet("s", rules, ".so").unwrap();
+        plants.step();
+        assert_eq!('o', plants.state_at(0));
+        plants.step();
+        assert_eq!(vec!['s', '.', 's'], (-1..=1).map(|pot| plants.state_at(pot)).collect::<Vec<_>>());
+        assert_eq!(vec![-1, 1], plants.live_pots());
+    }
+
+    #[test]
+    fn test_history() {
+        let plants = Plants::wolfram("#", 1, 90);
+        let history = plants.history(0..=3);
+        assert_eq!("     v
+0 ...#...
+1 ..#|#..
+2 .#.|.#.
+3 #.#|#.#
+", history.render());
+        assert_eq!(vec![(0, 0), (1, 0), (2, 0), (3, 0)], history.live_counts());
+
+        let mut image = vec![];
+        history.write_pbm(&mut image).unwrap();
+        assert_eq!("P1
+7 5
+0 0 0 1 0 0 0
+0 0 0 1 0 0 0
+0 0 1 0 1 0 0
+0 1 0 0 0 1 0
+1 0 1 0 1 0 1
+", String::from_utf8(image).unwrap());
+    }
+
+    #[test]
+    fn test_history_live_counts() {
+        let plants = Plants::new("#..#.#..##......###...###", EXAMPLE_RULES).unwrap();
+        let history = plants.history(18..=20);
+        assert_eq!(vec![18, 19, 20], history.generations.iter().map(|row| row.generation).collect::<Vec<_>>());
+        assert_eq!(325, history.live_counts()[2].1);
+        assert!(history.to_csv().ends_with("\n20,325\n"));
+    }
+
+    #[test]
+    #[should_panic(expected = "Odd rules")]
+    fn test_odd_wolfram_rule() {
+        Plants::wolfram("#", 1, 1);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(Err(ParseError::GrowsFromNothing { line: 1 }), Plants::new("#", "..... => #\n..#.. => #").map(|_plants| ()));
+        assert_eq!(Err(ParseError::MissingHeader), Plants::from_puzzle("#..#\n\n..#.. => #").map(|_plants| ()));
+        assert_eq!(Err(ParseError::BadRule { line: 4, text: "..#.. -> #".to_string() }),
+            Plants::from_puzzle("initial state: #..#\n\n.#... => #\n..#.. -> #").map(|_plants| ()));
+        assert_eq!(Err(ParseError::UnknownState { line: 2, state: 'x' }),
+            Plants::new("#", ".#... => #\n..x.. => #").map(|_plants| ()));
+        assert_eq!(Err(ParseError::EvenWidth { line: 1, width: 4 }), Plants::new("#", ".#.. => #").map(|_plants| ()));
+        assert_eq!(Err(ParseError::WrongWidth { line: 2, expected: 5, found: 3 }),
+            Plants::new("#", ".#... => #\n.#. => #").map(|_plants| ()));
+        assert_eq!(Err(ParseError::BadInitialState { position: 2, state: 'o' }),
+            Plants::new("#.o", "..#.. => #").map(|_plants| ()));
+        assert_eq!(Err(ParseError::BadAlphabet), Plants::with_alphabet("", "", "..#").map(|_plants| ()));
+        assert_eq!(Err(ParseError::TooManyNeighborhoods { width: 31 }),
+            Plants::new("#", &format!("{} => #", ".".repeat(30) + "#")).map(|_plants| ()));
+    }
+
+    #[test]
+    fn test_live_count_after_without_cycle() {
+        // Rule 90 keeps growing, so it never repeats
+        let plants = Plants::wolfram("#", 1, 90);
+        assert_eq!(None, plants.find_cycle(200));
+        assert_eq!(None, plants.live_count_after(50_000_000_000, 200));
+
+        let mut stepped = Plants::wolfram("#", 1, 90);
+        stepped.iterate(150);
+        assert_eq!(Some(stepped.live_count()), plants.live_count_after(150, 200));
+    }
+
+    #[test]
+    #[should_panic(expected = "radius 3")]
+    fn test_wolfram_radius_too_big() {
+        Plants::wolfram("#", 40, 0);
+    }
+}
@@ -2,118 +2,327 @@
 /// 
 /// Parse and trace mine-cart tracks and figure out when they'll collide
 
+use std::fmt;
+
 // Note that +x is right and +y is down
 
-#[derive(Clone)]
+/// Which way a cart goes at its next intersection
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Turn {
     Left,
     Straight,
     Right,
 }
 
-#[derive(Clone)]
+/// Which way a cart is facing, with north being up the map
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Heading {
+    North,
+    East,
+    South,
+    West,
+}
+
+impl Heading {
+    /// The heading a map character like '>' stands for, if it's a cart
+    fn from_symbol(c: char) -> Option<Heading> {
+        match c {
+            '^' => Some(Heading::North),
+            '>' => Some(Heading::East),
+            'v' => Some(Heading::South),
+            '<' => Some(Heading::West),
+            _ => None,
+        }
+    }
+
+    /// How a cart with this heading is drawn on the map
+    pub fn symbol(self) -> char {
+        match self {
+            Heading::North => '^',
+            Heading::East => '>',
+            Heading::South => 'v',
+            Heading::West => '<',
+        }
+    }
+
+    /// How far one step moves a cart in x and y
+    pub fn delta(self) -> (isize, isize) {
+        match self {
+            Heading::North => (0, -1),
+            Heading::East => (1, 0),
+            Heading::South => (0, 1),
+            Heading::West => (-1, 0),
+        }
+    }
+
+    pub fn turn_left(self) -> Heading {
+        match self {
+            Heading::North => Heading::West,
+            Heading::West => Heading::South,
+            Heading::South => Heading::East,
+            Heading::East => Heading::North,
+        }
+    }
+
+    pub fn turn_right(self) -> Heading {
+        match self {
+            Heading::North => Heading::East,
+            Heading::East => Heading::South,
+            Heading::South => Heading::West,
+            Heading::West => Heading::North,
+        }
+    }
+}
+
+/// A cart on the tracks.  `id` is its place in reading order on the
+/// original map, counting from 0.
+#[derive(Debug, Clone, PartialEq)]
 pub struct Cart {
-    x: usize,
-    y: usize,
-    next_turn: Turn,
-    direction: (isize, isize),
+    pub id: usize,
+    pub x: usize,
+    pub y: usize,
+    pub heading: Heading,
+    pub next_turn: Turn,
+}
+
+impl Cart {
+    /// Moves the cart one square along the tracks, turning if it lands
+    /// on a corner or an intersection
+    fn advance(&mut self, track: &Track) {
+        let (dx, dy) = self.heading.delta();
+        self.x = (self.x as isize + dx) as usize;
+        self.y = (self.y as isize + dy) as usize;
+        match track.at(self.x, self.y) {
+            '+' => {
+                match self.next_turn {
+                    Turn::Left => {
+                        self.heading = self.heading.turn_left();
+                        self.next_turn = Turn::Straight;
+                    },
+                    Turn::Straight => {
+                        self.next_turn = Turn::Right;
+                    },
+                    Turn::Right => {
+                        self.heading = self.heading.turn_right();
+                        self.next_turn = Turn::Left;
+                    }
+                }
+            },
+            '/' => {
+                self.heading = match self.heading {
+                    Heading::North | Heading::South => self.heading.turn_right(),
+                    Heading::East | Heading::West => self.heading.turn_left(),
+                }
+            },
+            '\\' => {
+                self.heading = match self.heading {
+                    Heading::North | Heading::South => self.heading.turn_left(),
+                    Heading::East | Heading::West => self.heading.turn_right(),
+                }
+            },
+            ' ' => panic!("Cart {} ran off the tracks at {},{}", self.id, self.x, self.y),
+            _ => (),
+        }
+    }
+}
+
+/// The tracks on their own, with the carts taken off
+/// 
+/// Squares under the carts are filled in with '-' or '|' depending on
+/// which way the cart is facing.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Track {
+    squares: Vec<Vec<char>>,
 }
 
-pub fn parse_map(map: &str) -> (Vec<Vec<char>>, Vec<Cart>) {
-    let mut positions: Vec<Vec<char>> = vec![];
-    let mut carts: Vec<Cart> = vec![];
-
-    for (y, line) in map.lines().enumerate() {
-        positions.push(vec![]);
-        for (x, c) in line.chars().enumerate() {
-            if c == '>' {
-                let cart = Cart { x, y, next_turn: Turn::Left, direction: (1, 0)};
-                carts.push(cart);
-                positions[y].push('-');
-            } else if c == '<' {
-                let cart = Cart { x, y, next_turn: Turn::Left, direction: (-1, 0) };
-                carts.push(cart);
-                positions[y].push('-');
-            } else if c == '^' {
-                let cart = Cart { x, y, next_turn: Turn::Left, direction: (0, -1) };
-                carts.push(cart);
-                positions[y].push('|');
-            } else if c == 'v' {
-                let cart = Cart { x, y, next_turn: Turn::Left, direction: (0, 1) };
-                carts.push(cart);
-                positions[y].push('|');
-            } else {
-                positions[y].push(c);
+impl Track {
+    /// Splits a map into the tracks and the carts on them
+    pub fn parse(map: &str) -> (Track, Vec<Cart>) {
+        let mut squares: Vec<Vec<char>> = vec![];
+        let mut carts: Vec<Cart> = vec![];
+
+        for (y, line) in map.lines().enumerate() {
+            squares.push(vec![]);
+            for (x, c) in line.chars().enumerate() {
+                match Heading::from_symbol(c) {
+                    Some(heading) => {
+                        carts.push(Cart { id: carts.len(), x, y, heading, next_turn: Turn::Left });
+                        squares[y].push(match heading {
+                            Heading::East | Heading::West => '-',
+                            Heading::North | Heading::South => '|',
+                        });
+                    },
+                    None => squares[y].push(c),
+                }
             }
         }
+        (Track { squares }, carts)
+    }
+
+    /// What's at a square, with anything off the map being blank
+    pub fn at(&self, x: usize, y: usize) -> char {
+        self.squares.get(y).and_then(|row| row.get(x)).cloned().unwrap_or(' ')
     }
-    (positions, carts)
 }
 
-pub fn find_crash(map: &str) -> (usize, usize) {
-    let (tracks, mut carts) = parse_map(map);
-    loop {
-        carts.sort_by_key(|cart| (cart.y, cart.x));
-
-        for i in 0..carts.len() {
-            {
-                let mut cart = &mut carts[i];
-                let (x, y) = cart.direction;
-                cart.x = (cart.x as isize + x) as usize;
-                cart.y = (cart.y as isize + y) as usize;
-                let location = tracks[cart.y][cart.x];
-                if location == '+' {
-                    match cart.next_turn {
-                        Turn::Left => {
-                            cart.direction = turn_left(cart.direction);
-                            cart.next_turn = Turn::Straight;
-                        },
-                        Turn::Straight => {
-                            cart.next_turn = Turn::Right;
-                        },
-                        Turn::Right => {
-                            cart.direction = turn_right(cart.direction);
-                            cart.next_turn = Turn::Left;
-                        }
-                    }
-                } else if location == '/' {
-                    match cart.direction {
-                        (0, -1) => cart.direction = (1, 0),
-                        (-1, 0) => cart.direction = (0, 1),
-                        (1, 0) => cart.direction = (0, -1),
-                        (0, 1) => cart.direction = (-1, 0),
-                        _ => panic!("Impossible direction"),
-                    }
-                } else if location == '\\' {
-                    match cart.direction {
-                        (1, 0) => cart.direction = (0, 1),
-                        (0, 1) => cart.direction = (1, 0),
-                        (-1, 0) => cart.direction = (0, -1),
-                        (0, -1) => cart.direction = (-1, 0),
-                        _ => panic!("Impossible direction"),
-                    }
+/// Two or more carts running into each other
+#[derive(Debug, Clone, PartialEq)]
+pub struct Collision {
+    pub tick: usize,
+    pub x: usize,
+    pub y: usize,
+    pub carts: Vec<usize>,
+}
+
+/// Something worth knowing about that happened during a tick
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event {
+    /// Carts crashed and were taken off the tracks
+    Crash(Collision),
+    /// Only one cart is left, at the end of this tick
+    LastCart { tick: usize, cart: Cart },
+    /// Every cart has crashed
+    AllCrashed { tick: usize },
+}
+
+/// Carts running around a track, one tick at a time
+/// 
+/// Carts that crash are gone straight away, so they don't get to move
+/// later in the same tick.
+#[derive(Debug, Clone)]
+pub struct Simulation {
+    track: Track,
+    carts: Vec<Cart>,
+    tick: usize,
+}
+
+impl Simulation {
+    pub fn new(map: &str) -> Self {
+        let (track, carts) = Track::parse(map);
+        Self { track, carts, tick: 0 }
+    }
+
+    /// How many ticks have been run so far
+    pub fn tick(&self) -> usize {
+        self.tick
+    }
+
+    pub fn track(&self) -> &Track {
+        &self.track
+    }
+
+    /// The carts that are still going, in the order they'll move next
+    /// tick (top to bottom, then left to right)
+    pub fn carts(&self) -> &[Cart] {
+        &self.carts
+    }
+
+    /// Moves every cart one square, in reading order, and reports any
+    /// crashes
+    /// 
+    /// There's also an event for the tick where the carts get down to
+    /// one or none.  Once that's happened, steps don't do anything.
+    pub fn step(&mut self) -> Vec<Event> {
+        if self.carts.len() <= 1 {
+            return vec![];
+        }
+        self.tick += 1;
+        self.carts.sort_by_key(|cart| (cart.y, cart.x));
+        let mut events = vec![];
+        let mut crashed = vec![false; self.carts.len()];
+
+        for i in 0..self.carts.len() {
+            if crashed[i] {
+                continue;
+            }
+            self.carts[i].advance(&self.track);
+            let (x, y) = (self.carts[i].x, self.carts[i].y);
+            let hit: Vec<usize> = (0..self.carts.len())
+                .filter(|&j| !crashed[j] && self.carts[j].x == x && self.carts[j].y == y)
+                .collect();
+            if hit.len() > 1 {
+                let mut ids: Vec<usize> = hit.iter().map(|&j| self.carts[j].id).collect();
+                ids.sort();
+                for j in hit {
+                    crashed[j] = true;
                 }
-            }    
-            let cart = &carts[i];
-            if carts.iter().filter(|c| c.x == cart.x && c.y == cart.y).count() > 1 {
-                return (cart.x, cart.y);
+                events.push(Event::Crash(Collision { tick: self.tick, x, y, carts: ids }));
             }
         }
+
+        let mut crashed = crashed.into_iter();
+        self.carts.retain(|_cart| !crashed.next().unwrap());
+        self.carts.sort_by_key(|cart| (cart.y, cart.x));
+        match self.carts.first() {
+            None => events.push(Event::AllCrashed { tick: self.tick }),
+            Some(cart) if self.carts.len() == 1 => events.push(Event::LastCart { tick: self.tick, cart: cart.clone() }),
+            _ => (),
+        }
+        events
+    }
+
+    /// Steps until `tick` ticks have been run (or the carts run out),
+    /// returning all the events along the way
+    pub fn run_to(&mut self, tick: usize) -> Vec<Event> {
+        let mut events = vec![];
+        while self.tick < tick && self.carts.len() > 1 {
+            events.extend(self.step());
+        }
+        events
+    }
+
+    /// Draws the track with the carts on it, like the puzzle's maps
+    pub fn render(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl fmt::Display for Simulation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (y, row) in self.track.squares.iter().enumerate() {
+            let line: String = row.iter().enumerate()
+                .map(|(x, &square)| {
+                    self.carts.iter()
+                        .find(|cart| cart.x == x && cart.y == y)
+                        .map_or(square, |cart| cart.heading.symbol())
+                })
+                .collect();
+            writeln!(f, "{}", line)?;
+        }
+        Ok(())
     }
 }
 
-fn turn_left(direction: (isize, isize)) -> (isize, isize) {
-    if direction == (1, 0) { (0, -1) }
-    else if direction == (0, -1) { (-1, 0) }
-    else if direction == (-1, 0) { (0, 1) }
-    else { (1, 0) }
+/// Where the first crash happens
+pub fn find_crash(map: &str) -> (usize, usize) {
+    let mut simulation = Simulation::new(map);
+    loop {
+        for event in simulation.step() {
+            if let Event::Crash(collision) = event {
+                return (collision.x, collision.y);
+            }
+        }
+        assert!(simulation.carts().len() > 1, "The carts never crash");
+    }
 }
 
-fn turn_right(direction: (isize, isize)) -> (isize, isize) {
-    if direction == (1, 0) { (0, 1) }
-    else if direction == (0, 1) { (-1, 0) }
-    else if direction == (-1, 0) { (0, -1) }
-    else { (1, 0) }
+/// Runs the carts, taking away any that crash, until there's at most
+/// one left
+/// 
+/// Returns where the last cart is at the end of the tick when it's
+/// left on its own (or None if the last ones all crashed together),
+/// along with every collision along the way.
+pub fn last_cart_standing(map: &str) -> (Option<(usize, usize)>, Vec<Collision>) {
+    let mut simulation = Simulation::new(map);
+    let mut collisions = vec![];
+    while simulation.carts().len() > 1 {
+        for event in simulation.step() {
+            if let Event::Crash(collision) = event {
+                collisions.push(collision);
+            }
+        }
+    }
+    (simulation.carts().first().map(|cart| (cart.x, cart.y)), collisions)
 }
 
 #[cfg(test)]
@@ -131,4 +340,59 @@ mod tests {
     
         assert_eq!((7, 3), find_crash(map));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_last_cart_standing() {
+        let map = "/>-<\\  
+|   |  
+| /<+-\\
+| | | v
+\\>+</ |
+  |   ^
+  \\<->/";
+
+        let (last, collisions) = last_cart_standing(map);
+        assert_eq!(Some((6, 4)), last);
+        assert_eq!(vec![
+            Collision { tick: 1, x: 2, y: 0, carts: vec![0, 1] },
+            Collision { tick: 1, x: 2, y: 4, carts: vec![4, 5] },
+            Collision { tick: 1, x: 6, y: 4, carts: vec![3, 6] },
+            Collision { tick: 3, x: 2, y: 4, carts: vec![2, 7] },
+        ], collisions);
+    }
+
+    #[test]
+    fn test_simulation() {
+        let map = "/->-\\        
+|   |  /----\\
+| /-+--+-\\  |
+| | |  | v  |
+\\-+-/  \\-+--/
+  \\------/   ";
+
+        let mut simulation = Simulation::new(map);
+        assert_eq!(0, simulation.tick());
+        assert!(simulation.run_to(3).is_empty());
+        assert_eq!("/---\\        
+|   v  /----\\
+| /-+--+-\\  |
+| | |  | |  |
+\\-+-/  \\-+->/
+  \\------/   
+", simulation.render());
+
+        let cart = &simulation.carts()[1];
+        assert_eq!((11, 4, Heading::East, Turn::Straight), (cart.x, cart.y, cart.heading, cart.next_turn));
+
+        let mut events = vec![];
+        while events.is_empty() {
+            events = simulation.step();
+        }
+        assert_eq!(14, simulation.tick());
+        assert_eq!(vec![
+            Event::Crash(Collision { tick: 14, x: 7, y: 3, carts: vec![0, 1] }),
+            Event::AllCrashed { tick: 14 },
+        ], events);
+        assert!(simulation.step().is_empty());
+    }
+}
@@ -4,30 +4,232 @@
 
 use std::collections::HashMap;
 use std::collections::HashSet;
+use std::io::{self, Write};
 
 // Part 1: Find the size of the largest non-infinite area'
 
+/// A way of measuring the distance between two X-Y points
+/// 
+/// Distances are floats so that metrics like Euclidean distance fit in
+/// alongside the integer ones.
+pub trait Metric {
+    fn distance(&self, from: (usize, usize), to: (usize, usize)) -> f64;
+}
+
+/// Absolute differences added together: taxicab distance
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Manhattan;
+
+impl Metric for Manhattan {
+    fn distance(&self, from: (usize, usize), to: (usize, usize)) -> f64 {
+        let (dx, dy) = differences(from, to);
+        dx + dy
+    }
+}
+
+/// The larger of the two absolute differences: a chess king's moves
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Chebyshev;
+
+impl Metric for Chebyshev {
+    fn distance(&self, from: (usize, usize), to: (usize, usize)) -> f64 {
+        let (dx, dy) = differences(from, to);
+        dx.max(dy)
+    }
+}
+
+/// Straight-line distance
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Euclidean;
+
+impl Metric for Euclidean {
+    fn distance(&self, from: (usize, usize), to: (usize, usize)) -> f64 {
+        let (dx, dy) = differences(from, to);
+        (dx*dx + dy*dy).sqrt()
+    }
+}
+
+/// Manhattan distance where moving along X and moving along Y can
+/// cost different amounts
+#[derive(Debug, Clone, Copy)]
+pub struct WeightedManhattan {
+    pub x_weight: f64,
+    pub y_weight: f64,
+}
+
+impl WeightedManhattan {
+    pub fn new(x_weight: f64, y_weight: f64) -> Self {
+        Self { x_weight, y_weight }
+    }
+}
+
+impl Metric for WeightedManhattan {
+    fn distance(&self, from: (usize, usize), to: (usize, usize)) -> f64 {
+        let (dx, dy) = differences(from, to);
+        self.x_weight*dx + self.y_weight*dy
+    }
+}
+
+/// Absolute X and Y differences between two points
+fn differences(from: (usize, usize), to: (usize, usize)) -> (f64, f64) {
+    let dx = (from.0 as f64 - to.0 as f64).abs();
+    let dy = (from.1 as f64 - to.1 as f64).abs();
+    (dx, dy)
+}
+
 /// A grid of X-Y coordinates and unclaimed points
 /// 
 /// coords is a vector of Coordinates.  Their index is their "ID number"
 /// points is a vector of unclaimed points.  Their value is the ID of the 
 ///     closest Coordinate
-struct Grid {
+/// metric is how distances get measured for both parts
+pub struct Grid<M: Metric = Manhattan> {
     coords: Vec<Coordinate>,
     points: Vec<Option<usize>>,
     width: usize,
     height: usize,
+    metric: M,
+}
+
+impl Default for Grid {
+    /// An empty grid measured with Manhattan distance
+    fn default() -> Self {
+        Grid::new()
+    }
 }
 
 impl Grid {
     pub fn new() -> Self {
-        Self { coords: vec![], points: vec![], width: 0, height: 0 }
+        Grid::with_metric(Manhattan)
     }
 
     /// Loads a grid from text, building each coordinate and calculating
     /// most of part 1
     pub fn from_text(text: &str) -> Self {
-        let mut grid = Grid::new();
+        Grid::from_text_with_metric(text, Manhattan)
+    }
+
+    /// Counts every point on the whole (unbounded) plane whose total
+    /// manhattan distance to all Coordinates is less than `dist`
+    /// 
+    /// Unlike `squares_closer_than`, this isn't limited to the
+    /// bounding box, so big thresholds don't get undercounted.
+    pub fn safe_region_size(&self, dist: usize) -> usize {
+        self.safe_columns(dist).iter()
+            .map(|&(_x, low, high)| (high - low + 1) as usize)
+            .sum()
+    }
+
+    /// Every point in the safe region, column by column
+    pub fn safe_region(&self, dist: usize) -> Vec<(i64, i64)> {
+        self.safe_columns(dist).iter()
+            .flat_map(|&(x, low, high)| (low..=high).map(move |y| (x, y)))
+            .collect()
+    }
+
+    /// The points in the safe region that have at least one neighbor
+    /// (up, down, left, or right) outside of it
+    pub fn safe_region_outline(&self, dist: usize) -> Vec<(i64, i64)> {
+        let columns = self.safe_columns(dist);
+        let inside = |column: Option<&(i64, i64, i64)>, y: i64| {
+            column.is_some_and(|&(_x, low, high)| low <= y && y <= high)
+        };
+        let mut outline = vec![];
+        for (i, &(x, low, high)) in columns.iter().enumerate() {
+            // Columns are contiguous, so the neighbors are right next door
+            let left = if i > 0 { columns.get(i - 1) } else { None };
+            let right = columns.get(i + 1);
+            for y in low..=high {
+                if y == low || y == high || !inside(left, y) || !inside(right, y) {
+                    outline.push((x, y));
+                }
+            }
+        }
+        outline
+    }
+
+    /// Splits the safe region into columns of (x, lowest y, highest y)
+    /// 
+    /// Manhattan distance separates into an X part and a Y part, and
+    /// each part is convex, so every column of the region is one
+    /// unbroken run of Y values.
+    fn safe_columns(&self, dist: usize) -> Vec<(i64, i64, i64)> {
+        if self.coords.is_empty() {
+            return vec![];
+        }
+        let dist = dist as i64;
+        let xs = AxisDistances::new(self.coords.iter().map(|coord| coord.x as i64).collect());
+        let ys = AxisDistances::new(self.coords.iter().map(|coord| coord.y as i64).collect());
+
+        let y_values: Vec<i64> = ys.candidates(dist).collect();
+        let y_totals: Vec<i64> = y_values.iter().map(|&y| ys.total(y)).collect();
+        let closest = (0..y_totals.len()).min_by_key(|&i| y_totals[i]).unwrap();
+
+        let mut columns = vec![];
+        for x in xs.candidates(dist) {
+            let remaining = dist - xs.total(x);
+            if y_totals[closest] >= remaining {
+                continue;
+            }
+            // Totals go down until the closest y, then back up
+            let low = y_totals[..closest].partition_point(|&total| total >= remaining);
+            let high = closest + y_totals[closest..].partition_point(|&total| total < remaining) - 1;
+            columns.push((x, y_values[low], y_values[high]));
+        }
+        columns
+    }
+}
+
+/// Total distances to a set of points along a single axis
+/// 
+/// Keeps the points sorted with running totals so the sum of distances
+/// to any position is a binary search away.
+struct AxisDistances {
+    sorted: Vec<i64>,
+    prefix: Vec<i64>,
+}
+
+impl AxisDistances {
+    fn new(mut values: Vec<i64>) -> Self {
+        values.sort();
+        let mut prefix = vec![0];
+        for value in values.iter() {
+            let last = *prefix.last().unwrap();
+            prefix.push(last + value);
+        }
+        Self { sorted: values, prefix }
+    }
+
+    /// Sum of the distances from `position` to every point
+    fn total(&self, position: i64) -> i64 {
+        let n = self.sorted.len() as i64;
+        let below = self.sorted.partition_point(|&value| value <= position);
+        let below_sum = self.prefix[below];
+        let above_sum = self.prefix[self.sorted.len()] - below_sum;
+        let below = below as i64;
+        position*below - below_sum + above_sum - position*(n - below)
+    }
+
+    /// Every position whose total could possibly be under `dist`
+    /// 
+    /// Past the outermost points, each step adds one per point, so
+    /// nothing further than dist/n beyond them can make the cut.
+    fn candidates(&self, dist: i64) -> std::ops::RangeInclusive<i64> {
+        let reach = dist / self.sorted.len() as i64 + 1;
+        (self.sorted[0] - reach)..=(self.sorted[self.sorted.len() - 1] + reach)
+    }
+}
+
+impl<M: Metric> Grid<M> {
+    /// An empty grid that measures distances with `metric`
+    pub fn with_metric(metric: M) -> Self {
+        Self { coords: vec![], points: vec![], width: 0, height: 0, metric }
+    }
+
+    /// Loads a grid from text like `from_text`, but measures distances
+    /// with `metric`
+    pub fn from_text_with_metric(text: &str, metric: M) -> Self {
+        let mut grid = Grid::with_metric(metric);
         for line in text.lines() {
             let mut coord = Coordinate::from_str(line);
             coord.id = grid.coords.len();
@@ -55,9 +257,9 @@ impl Grid {
     fn calculate_closest_coords(&mut self) {
         for y in 0..self.height {
             for x in 0..self.width {
-                let mut min_dist = self.width + self.height;
+                let mut min_dist = f64::INFINITY;
                 for coord in self.coords.iter() {
-                    let dist = coord.manhattan_distance_to(x + 1, y + 1);
+                    let dist = coord.distance_to(&self.metric, x + 1, y + 1);
                     if dist < min_dist {
                         min_dist = dist;
                         self.points[x + y*self.width] = Some(coord.id);
@@ -74,27 +276,31 @@ impl Grid {
     /// 
     /// Internal coordinates are completely fenced in by other
     /// coordinates.  No infinite boundaries (i.e. not touching the edges)
-    /// 
-    /// This could probably be batched in the contructor rather than done in a loop.
     fn is_internal(&self, id: usize) -> bool {
+        !self.external_ids().contains(&id)
+    }
+
+    /// Collects the IDs of every coordinate that claims a point on the
+    /// edge of the grid, meaning its area goes on forever
+    fn external_ids(&self) -> HashSet<usize> {
         let mut external: HashSet<usize> = HashSet::new();
         // Left and right side
         for y in 0..self.height {
-            let left = self.points[0 + y*self.width];
+            let left = self.points[y*self.width];
             let right = self.points[y*self.width + self.width - 1];
-            if left.is_some() { external.insert(left.unwrap()); }
-            if right.is_some() { external.insert(right.unwrap()); }
+            if let Some(id) = left { external.insert(id); }
+            if let Some(id) = right { external.insert(id); }
         }
 
         // Top and bottom
         for x in 0..self.width {
             let top = self.points[x];
             let bottom = self.points[x + (self.height - 1)*self.width];
-            if top.is_some() { external.insert(top.unwrap()); }
-            if bottom.is_some() { external.insert(bottom.unwrap()); }
+            if let Some(id) = top { external.insert(id); }
+            if let Some(id) = bottom { external.insert(id); }
         }
 
-        !external.contains(&id)
+        external
     }
 
     /// Calculates the area of the internal coordinate that claims the most area
@@ -111,21 +317,140 @@ impl Grid {
             .max().unwrap()
     }
 
-    /// Counts how many points have a total manhattan distance less than
+    /// Counts how many points have a total distance less than
     /// a threshold when checked against all Coordinates
     pub fn squares_closer_than(&self, dist: usize) -> usize {
-        let mut distances: Vec<usize> = vec![];
+        let mut distances: Vec<f64> = vec![];
         for y in 0..self.height {
             for x in 0..self.width {
                 let total = self.coords.iter()
-                    .fold(0, |acc, coord| acc + coord.manhattan_distance_to(x, y));
-                if total < dist {
+                    .fold(0.0, |acc, coord| acc + coord.distance_to(&self.metric, x, y));
+                if total < dist as f64 {
                     distances.push(total);
                 }
             }
         }
         distances.iter().count()
     }
+
+    /// Total distance from an X-Y pair to every Coordinate
+    fn total_distance(&self, x: usize, y: usize) -> f64 {
+        self.coords.iter()
+            .map(|coord| coord.distance_to(&self.metric, x, y))
+            .sum()
+    }
+
+    /// Draws the map of who owns each point as text
+    /// 
+    /// Each coordinate gets a letter (cycling through the alphabet by ID).
+    /// Points owned by coordinates with finite areas are uppercase,
+    /// points owned by coordinates with infinite areas are lowercase,
+    /// the coordinates themselves are '#', and ties are '.'.  If
+    /// `safe_dist` is given, points in the safe region (total distance
+    /// less than `safe_dist`) are drawn as '+' instead.
+    pub fn render(&self, safe_dist: Option<usize>) -> String {
+        let external = self.external_ids();
+        let mut result = String::with_capacity((self.width + 1)*self.height);
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let c = match self.cell(x, y, safe_dist) {
+                    Cell::Coordinate => '#',
+                    Cell::Safe => '+',
+                    Cell::Tie => '.',
+                    Cell::Owned(id) => {
+                        let letter = (b'a' + (id % 26) as u8) as char;
+                        if external.contains(&id) { letter } else { letter.to_ascii_uppercase() }
+                    }
+                };
+                result.push(c);
+            }
+            result.push('\n');
+        }
+        result
+    }
+
+    /// Writes the ownership map as a binary PPM (P6) image, one pixel
+    /// per point
+    /// 
+    /// Every coordinate gets its own colour.  Coordinates with infinite
+    /// areas are drawn darker, ties are black, the coordinates
+    /// themselves are white, and the safe region (if `safe_dist` is
+    /// given) is washed out toward white.
+    pub fn write_ppm<W: Write>(&self, out: &mut W, safe_dist: Option<usize>) -> io::Result<()> {
+        let external = self.external_ids();
+        write!(out, "P6\n{} {}\n255\n", self.width, self.height)?;
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let owner = self.points[x + y*self.width];
+                let color = match self.cell(x, y, safe_dist) {
+                    Cell::Coordinate => [255, 255, 255],
+                    Cell::Tie => [0, 0, 0],
+                    Cell::Owned(id) if external.contains(&id) => darken(color_for(id)),
+                    Cell::Owned(id) => color_for(id),
+                    Cell::Safe => match owner {
+                        Some(id) => wash_out(color_for(id)),
+                        None => wash_out([0, 0, 0]),
+                    }
+                };
+                out.write_all(&color)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Figures out what to draw at a point on the grid
+    fn cell(&self, x: usize, y: usize, safe_dist: Option<usize>) -> Cell {
+        // Points are shifted by one from the coordinates they're measured at
+        if self.coords.iter().any(|coord| coord.x == x + 1 && coord.y == y + 1) {
+            return Cell::Coordinate;
+        }
+        if let Some(dist) = safe_dist {
+            if self.total_distance(x + 1, y + 1) < dist as f64 {
+                return Cell::Safe;
+            }
+        }
+        match self.points[x + y*self.width] {
+            Some(id) => Cell::Owned(id),
+            None => Cell::Tie,
+        }
+    }
+}
+
+/// What shows up at a single point on a rendered Grid
+enum Cell {
+    Coordinate,
+    Owned(usize),
+    Tie,
+    Safe,
+}
+
+/// Picks a bright, distinct colour for a coordinate ID by walking
+/// around the colour wheel by the golden angle
+fn color_for(id: usize) -> [u8; 3] {
+    let hue = (id as f64 * 137.508) % 360.0;
+    let sector = hue / 60.0;
+    let rising = (255.0 * (sector % 1.0)) as u8;
+    let falling = 255 - rising;
+    match sector as usize {
+        0 => [255, rising, 0],
+        1 => [falling, 255, 0],
+        2 => [0, 255, rising],
+        3 => [0, falling, 255],
+        4 => [rising, 0, 255],
+        _ => [255, 0, falling],
+    }
+}
+
+fn darken(color: [u8; 3]) -> [u8; 3] {
+    [color[0] / 3, color[1] / 3, color[2] / 3]
+}
+
+fn wash_out(color: [u8; 3]) -> [u8; 3] {
+    [
+        ((color[0] as u16 + 3*255) / 4) as u8,
+        ((color[1] as u16 + 3*255) / 4) as u8,
+        ((color[2] as u16 + 3*255) / 4) as u8,
+    ]
 }
 
 /// An X-Y coordinate on a Grid
@@ -156,6 +481,11 @@ impl Coordinate {
         let y2 = y as i32;
         ((x2 - x1).abs() + (y2 - y1).abs()) as usize
     }
+
+    /// Calculate the distance from here to any X-Y pair using any Metric
+    pub fn distance_to<M: Metric>(&self, metric: &M, x: usize, y: usize) -> f64 {
+        metric.distance((self.x, self.y), (x, y))
+    }
 }
 
 /// Part 1
@@ -170,6 +500,38 @@ pub fn squares_closer_than(text: &str, dist: usize) -> usize {
     grid.squares_closer_than(dist)
 }
 
+/// Part 2, counting the safe region out past the edges of the grid
+pub fn safe_region_size(text: &str, dist: usize) -> usize {
+    let grid = Grid::from_text(text);
+    grid.safe_region_size(dist)
+}
+
+/// Draws the ownership map, with the safe region overlaid if a
+/// threshold is given
+pub fn render_map(text: &str, safe_dist: Option<usize>) -> String {
+    let grid = Grid::from_text(text);
+    grid.render(safe_dist)
+}
+
+/// Writes the ownership map as a PPM image, with the safe region
+/// washed out if a threshold is given
+pub fn write_map_ppm<W: Write>(text: &str, out: &mut W, safe_dist: Option<usize>) -> io::Result<()> {
+    let grid = Grid::from_text(text);
+    grid.write_ppm(out, safe_dist)
+}
+
+/// Part 1, measuring with any Metric
+pub fn largest_finite_area_with<M: Metric>(text: &str, metric: M) -> usize {
+    let grid = Grid::from_text_with_metric(text, metric);
+    grid.most_claimed_area()
+}
+
+/// Part 2, measuring with any Metric
+pub fn squares_closer_than_with<M: Metric>(text: &str, dist: usize, metric: M) -> usize {
+    let grid = Grid::from_text_with_metric(text, metric);
+    grid.squares_closer_than(dist)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -197,4 +559,108 @@ mod tests {
 
         assert_eq!(16, squares_closer_than(coords, 32));
     }
+
+    #[test]
+    fn test_metrics() {
+        assert_eq!(7.0, Manhattan.distance((1, 1), (4, 5)));
+        assert_eq!(4.0, Chebyshev.distance((1, 1), (4, 5)));
+        assert_eq!(5.0, Euclidean.distance((1, 1), (4, 5)));
+        assert_eq!(11.0, WeightedManhattan::new(1.0, 2.0).distance((1, 1), (4, 5)));
+    }
+
+    #[test]
+    fn test_unit_weights_match_manhattan() {
+        let coords = "1, 1
+1, 6
+8, 3
+3, 4
+5, 5
+8, 9";
+
+        let weights = WeightedManhattan::new(1.0, 1.0);
+        assert_eq!(17, largest_finite_area_with(coords, weights));
+        assert_eq!(16, squares_closer_than_with(coords, 32, weights));
+    }
+
+    #[test]
+    fn test_safe_region_past_the_edges() {
+        let coords = "1, 1
+1, 6
+8, 3
+3, 4
+5, 5
+8, 9";
+
+        assert_eq!(16, safe_region_size(coords, 32));
+
+        // Brute force it over a window big enough to hold everything
+        let grid = Grid::from_text(coords);
+        let mut expected = 0;
+        for y in -50..60 {
+            for x in -50..60 {
+                let total: i64 = grid.coords.iter()
+                    .map(|coord| (coord.x as i64 - x).abs() + (coord.y as i64 - y).abs())
+                    .sum();
+                if total < 100 {
+                    expected += 1;
+                }
+            }
+        }
+        assert!(expected > squares_closer_than(coords, 100));
+        assert_eq!(expected, safe_region_size(coords, 100));
+        assert_eq!(expected, grid.safe_region(100).len());
+    }
+
+    #[test]
+    fn test_render() {
+        let coords = "1, 1
+1, 6
+8, 3
+3, 4
+5, 5
+8, 9";
+
+        let expected = "#aaa.ccc
+aaDDEccc
+aDDDEcc#
+.D#DEEcc
+b.DE#EEc
+#b.EEEE.
+bb.EEEff
+bb.EEfff
+bb.ffff#
+";
+        assert_eq!(expected, render_map(coords, None));
+
+        // Two of the coordinates sit inside the safe region themselves
+        let safe = render_map(coords, Some(32));
+        assert_eq!(14, safe.matches('+').count());
+    }
+
+    #[test]
+    fn test_write_ppm() {
+        let grid = Grid::from_text("1, 1\n3, 2");
+        let mut image = vec![];
+        grid.write_ppm(&mut image, None).unwrap();
+        let header = b"P6\n3 2\n255\n";
+        assert_eq!(&header[..], &image[..header.len()]);
+        assert_eq!(header.len() + 3*2*3, image.len());
+        assert_eq!(&[255, 255, 255], &image[header.len()..header.len() + 3]);
+
+        let mut from_text = vec![];
+        write_map_ppm("1, 1\n3, 2", &mut from_text, None).unwrap();
+        assert_eq!(image, from_text);
+    }
+
+    #[test]
+    fn test_safe_region_outline() {
+        let grid = Grid::from_text("3, 3");
+        let mut region = grid.safe_region(2);
+        region.sort();
+        assert_eq!(vec![(2, 3), (3, 2), (3, 3), (3, 4), (4, 3)], region);
+
+        let mut outline = grid.safe_region_outline(2);
+        outline.sort();
+        assert_eq!(vec![(2, 3), (3, 2), (3, 4), (4, 3)], outline);
+    }
 }
\ No newline at end of file
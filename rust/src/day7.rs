@@ -4,93 +4,605 @@
 
 use std::collections::{HashMap, HashSet};
 use std::cmp;
+use std::ops::RangeInclusive;
 
 // Part 1: In what order should the steps be completed?
 
-struct DependencyGraph {
-    instructions: HashMap<char, Vec<char>>,
+/// Things that can keep a set of steps from ever getting finished
+#[derive(Debug, PartialEq)]
+pub enum ScheduleError {
+    /// These steps depend on each other in a loop, each one waiting on
+    /// the one before it (and the first waiting on the last)
+    Cycle(Vec<String>),
+    /// There's work to do but nobody to do it
+    NoWorkers,
+    /// The instructions couldn't be read
+    Parse(ParseError),
+    /// This step isn't a single capital letter and was never given a
+    /// duration
+    MissingDuration(String),
+}
+
+/// Ways that instructions or durations can fail to make sense.  Lines
+/// are counted from 1.
+#[derive(Debug, PartialEq)]
+pub enum ParseError {
+    /// A line that isn't any kind of instruction
+    UnknownInstruction { line: usize, text: String },
+    /// A duration that isn't a whole number of seconds
+    BadDuration { line: usize, token: String },
+    /// A line in a durations list with a step name but no duration
+    MissingDuration { line: usize },
+}
+
+impl From<ParseError> for ScheduleError {
+    fn from(error: ParseError) -> Self {
+        ScheduleError::Parse(error)
+    }
+}
+
+/// One step done by one worker
+#[derive(Debug, Clone, PartialEq)]
+pub struct Assignment {
+    pub worker: usize,
+    pub task: String,
+    pub start: usize,
+    pub end: usize,
+}
+
+/// Everything that happened while working through the steps
+#[derive(Debug)]
+pub struct Schedule {
+    pub workers: usize,
+    pub assignments: Vec<Assignment>,
+    pub makespan: usize,
+    pub critical_path: Vec<String>,
+    pub critical_length: usize,
+}
+
+impl Schedule {
+    /// The steps one worker did, in order
+    pub fn worker_assignments(&self, worker: usize) -> Vec<&Assignment> {
+        let mut jobs: Vec<&Assignment> = self.assignments.iter()
+            .filter(|job| job.worker == worker)
+            .collect();
+        jobs.sort_by_key(|job| job.start);
+        jobs
+    }
+
+    /// Stretches of time (worker, start, end) when a worker had nothing to do
+    pub fn idle_periods(&self) -> Vec<(usize, usize, usize)> {
+        let mut idle = vec![];
+        for worker in 0..self.workers {
+            let mut clock = 0;
+            for job in self.worker_assignments(worker) {
+                if job.start > clock {
+                    idle.push((worker, clock, job.start));
+                }
+                clock = job.end;
+            }
+            if clock < self.makespan {
+                idle.push((worker, clock, self.makespan));
+            }
+        }
+        idle
+    }
+
+    /// Draws one row per worker, one column per `seconds_per_column`
+    /// seconds, marking each step with a symbol and idle time with '.'
+    /// 
+    /// A column shows whichever step took up most of its time, so short
+    /// steps still show up in wide columns.  If every step's name is a
+    /// single character, that's its symbol.  Otherwise steps are given
+    /// letters in the order they started, and a legend follows the chart.
+    /// 
+    /// Panics if `seconds_per_column` is 0.
+    pub fn gantt(&self, seconds_per_column: usize) -> String {
+        assert!(seconds_per_column > 0, "A Gantt chart column has to cover at least one second");
+        let symbols = self.gantt_symbols();
+        let columns = self.makespan.div_ceil(seconds_per_column);
+        let label_width = format!("{}", self.workers.saturating_sub(1)).len();
+        let mut result = String::new();
+        for worker in 0..self.workers {
+            let jobs = self.worker_assignments(worker);
+            result.push_str(&format!("Worker {:>width$} |", worker, width = label_width));
+            for column in 0..columns {
+                let from = column * seconds_per_column;
+                let to = from + seconds_per_column;
+                // Ties go to the step that started first
+                let c = jobs.iter()
+                    .map(|job| (cmp::min(job.end, to).saturating_sub(cmp::max(job.start, from)), job))
+                    .filter(|(overlap, _job)| *overlap > 0)
+                    .max_by_key(|(overlap, job)| (*overlap, cmp::Reverse(job.start)))
+                    .map_or('.', |(_overlap, job)| symbols[job.task.as_str()]);
+                result.push(c);
+            }
+            result.push_str("|\n");
+        }
+
+        let mut legend: Vec<(char, &str)> = symbols.iter()
+            .filter(|(task, symbol)| task.chars().ne(Some(**symbol)))
+            .map(|(task, symbol)| (*symbol, *task))
+            .collect();
+        legend.sort();
+        for (symbol, task) in legend {
+            result.push_str(&format!("{} = {}\n", symbol, task));
+        }
+        result
+    }
+
+    /// Picks the character each step is drawn with in `gantt`
+    fn gantt_symbols(&self) -> HashMap<&str, char> {
+        let mut jobs: Vec<&Assignment> = self.assignments.iter().collect();
+        jobs.sort_by_key(|job| (job.start, job.worker));
+        if jobs.iter().all(|job| job.task.chars().count() == 1) {
+            return jobs.iter().map(|job| (job.task.as_str(), job.task.chars().next().unwrap())).collect();
+        }
+        let mut letters = ('A'..='Z').chain('a'..='z').chain('0'..='9');
+        let mut symbols = HashMap::new();
+        for job in jobs {
+            if !symbols.contains_key(job.task.as_str()) {
+                symbols.insert(job.task.as_str(), letters.next().unwrap_or('?'));
+            }
+        }
+        symbols
+    }
+
+    /// What fraction of the available worker time was spent working
+    pub fn utilisation(&self) -> f64 {
+        if self.workers == 0 || self.makespan == 0 {
+            return 0.0;
+        }
+        let busy: usize = self.assignments.iter().map(|job| job.end - job.start).sum();
+        busy as f64 / (self.workers * self.makespan) as f64
+    }
+
+    /// Lists every assignment as "worker,task,start,end" rows, in the
+    /// order they were handed out
+    pub fn to_csv(&self) -> String {
+        let mut result = String::from("worker,task,start,end\n");
+        for job in self.assignments.iter() {
+            result.push_str(&format!("{},{},{},{}\n", job.worker, job.task, job.start, job.end));
+        }
+        result
+    }
+}
+
+/// A way of deciding which of the ready steps gets done first
+/// 
+/// Anything a policy wants to work out once per run (rather than every
+/// time it picks a step) goes in its Context.
+pub trait Policy {
+    type Context;
+
+    /// Called once at the start of every run over `graph`, before
+    /// picking any steps
+    fn prepare(&self, graph: &DependencyGraph) -> Self::Context;
+
+    /// Puts `ready` in the order the steps should be picked up
+    /// 
+    /// The steps come in alphabetical order, so a stable sort leaves
+    /// ties alphabetical.
+    fn prioritize(&self, graph: &DependencyGraph, context: &Self::Context, ready: &mut [&str]);
+}
+
+/// Earliest in the alphabet first (the puzzle's rule)
+pub struct Alphabetical;
+
+impl Policy for Alphabetical {
+    type Context = ();
+
+    fn prepare(&self, _graph: &DependencyGraph) {}
+
+    fn prioritize(&self, _graph: &DependencyGraph, _context: &(), ready: &mut [&str]) {
+        ready.sort();
+    }
+}
+
+/// Slowest steps first, with any steps missing a duration last
+pub struct LongestFirst;
+
+impl Policy for LongestFirst {
+    type Context = ();
+
+    fn prepare(&self, _graph: &DependencyGraph) {}
+
+    fn prioritize(&self, graph: &DependencyGraph, _context: &(), ready: &mut [&str]) {
+        ready.sort_by_key(|task| cmp::Reverse(graph.task_duration(task).ok()));
+    }
+}
+
+/// Steps that the most other steps are directly waiting on first
+pub struct MostDependentsFirst;
+
+impl Policy for MostDependentsFirst {
+    type Context = ();
+
+    fn prepare(&self, _graph: &DependencyGraph) {}
+
+    fn prioritize(&self, graph: &DependencyGraph, _context: &(), ready: &mut [&str]) {
+        ready.sort_by_key(|task| cmp::Reverse(graph.dependents(task)));
+    }
+}
+
+/// Steps with the longest chain of work still hanging off of them first
+/// 
+/// The chain lengths are worked out once per run, in `prepare`.
+pub struct CriticalPathFirst {
+    pub base_delay: usize,
+}
+
+impl Policy for CriticalPathFirst {
+    type Context = HashMap<String, usize>;
+
+    fn prepare(&self, graph: &DependencyGraph) -> HashMap<String, usize> {
+        graph.tail_lengths(self.base_delay).into_iter()
+            .map(|(task, tail)| (task.to_string(), tail))
+            .collect()
+    }
+
+    fn prioritize(&self, _graph: &DependencyGraph, tails: &HashMap<String, usize>, ready: &mut [&str]) {
+        ready.sort_by_key(|task| cmp::Reverse(tails[*task]));
+    }
+}
+
+/// Hand-picked priorities: higher numbers go first, and anything not
+/// listed counts as 0
+pub struct Priority(pub HashMap<String, i64>);
+
+impl Policy for Priority {
+    type Context = ();
+
+    fn prepare(&self, _graph: &DependencyGraph) {}
+
+    fn prioritize(&self, _graph: &DependencyGraph, _context: &(), ready: &mut [&str]) {
+        ready.sort_by_key(|task| cmp::Reverse(self.0.get(*task).cloned().unwrap_or(0)));
+    }
+}
+
+/// How one combination of workers and base delay played out
+#[derive(Debug, Clone, PartialEq)]
+pub struct SweepResult {
+    pub workers: usize,
+    pub base_delay: usize,
+    pub makespan: usize,
+    pub utilisation: f64,
+}
+
+pub struct DependencyGraph {
+    instructions: HashMap<String, Vec<String>>,
+    durations: HashMap<String, usize>,
+}
+
+impl Default for DependencyGraph {
+    /// A graph with no steps in it
+    fn default() -> Self {
+        DependencyGraph::new()
+    }
 }
 
 impl DependencyGraph {
     pub fn new() -> Self {
-        Self { instructions: HashMap::new() }
+        Self { instructions: HashMap::new(), durations: HashMap::new() }
     }
 
-    pub fn from_instructions(text: &str) -> Self {
+    /// Reads lines like "Step A must be finished before step B can begin."
+    /// 
+    /// Step names can be any word, not just a single letter.  Lines like
+    /// "Step A takes 12 seconds." set how long a step takes.
+    pub fn from_instructions(text: &str) -> Result<Self, ParseError> {
         let mut deps = DependencyGraph::new();
-        for line in text.lines() {
-            let parent = line.chars().take(6).last().unwrap();
-            let child = line.chars().take(37).last().unwrap();
-            deps.add_dependency(parent, child);
+        for (index, line) in text.lines().enumerate() {
+            let words: Vec<&str> = line.split_whitespace().collect();
+            match words.as_slice() {
+                [] => continue,
+                ["Step", task, "takes", duration, ..] => {
+                    deps.set_duration(task, DependencyGraph::parse_duration(index + 1, duration)?);
+                }
+                ["Step", parent, "must", "be", "finished", "before", "step", child, ..] => {
+                    deps.add_dependency(parent, child);
+                }
+                _ => return Err(ParseError::UnknownInstruction { line: index + 1, text: line.to_string() }),
+            }
         }
-        deps
+        Ok(deps)
+    }
+
+    /// Reads step durations from text with one "name duration" pair
+    /// per line.  Blank lines are skipped.
+    pub fn load_durations(&mut self, text: &str) -> Result<(), ParseError> {
+        for (index, line) in text.lines().enumerate() {
+            let words: Vec<&str> = line.split_whitespace().collect();
+            match words.as_slice() {
+                [] => continue,
+                [task, duration] => {
+                    self.set_duration(task, DependencyGraph::parse_duration(index + 1, duration)?);
+                }
+                [_task] => return Err(ParseError::MissingDuration { line: index + 1 }),
+                _ => return Err(ParseError::UnknownInstruction { line: index + 1, text: line.to_string() }),
+            }
+        }
+        Ok(())
+    }
+
+    fn parse_duration(line: usize, token: &str) -> Result<usize, ParseError> {
+        token.parse().map_err(|_| ParseError::BadDuration { line, token: token.to_string() })
+    }
+
+    pub fn add_task(&mut self, task: &str) {
+        self.instructions.entry(task.to_string()).or_default();
+    }
+
+    pub fn add_dependency(&mut self, parent: &str, child: &str) {
+        self.add_task(parent);
+        let child_deps = self.instructions.entry(child.to_string()).or_default();
+        child_deps.push(parent.to_string());
     }
 
-    pub fn add_dependency(&mut self, parent: char, child: char) {
-        self.instructions.entry(parent).or_insert(vec![]);
-        let child_deps = self.instructions.entry(child).or_insert(vec![]);
-        child_deps.push(parent);
+    pub fn set_duration(&mut self, task: &str, duration: usize) {
+        self.add_task(task);
+        self.durations.insert(task.to_string(), duration);
     }
 
-    pub fn linearize(&self) -> Vec<char> {
-        let mut results: Vec<char> = vec![];
-        let mut pending: HashSet<char> = self.instructions.keys().cloned().collect();
-        while pending.len() > 0 {
-            let mut satisfied: Vec<char> = self.instructions.iter()
-                .filter(|(c, deps)| {
-                    pending.contains(c) &&
-                    deps.iter().all(|dep| !pending.contains(dep))
-                })
-                .map(|(c, deps)| c.clone())
-                .collect();
-            satisfied.sort();
-            results.push(satisfied[0]);
-            pending.remove(&satisfied[0]);
+    pub fn linearize(&self) -> Result<Vec<String>, ScheduleError> {
+        self.linearize_with(&Alphabetical)
+    }
+
+    /// Puts the steps in order, using `policy` to pick between steps
+    /// that are ready at the same time
+    pub fn linearize_with<P: Policy>(&self, policy: &P) -> Result<Vec<String>, ScheduleError> {
+        let context = policy.prepare(self);
+        let mut results: Vec<String> = vec![];
+        let mut pending: HashSet<&str> = self.instructions.keys().map(|task| task.as_str()).collect();
+        while !pending.is_empty() {
+            let mut satisfied = self.ready_tasks(&pending, &HashSet::new());
+            policy.prioritize(self, &context, &mut satisfied);
+            let next = match satisfied.first() {
+                Some(task) => *task,
+                None => return Err(self.find_cycle(&pending)),
+            };
+            results.push(next.to_string());
+            pending.remove(next);
         }
-        results
+        Ok(results)
     }
 
     /// Calculate how long it would take if each step has a duration and
     /// you have some elves helping you
-    pub fn assisted_assembly_duration(&self, workers: usize, base_delay: usize) -> usize {
-        let mut pending: HashSet<char> = self.instructions.keys().cloned().collect();
-        let mut active: HashMap<char, usize> = HashMap::new();
-        let mut clock: usize = 0;
-        
-        while pending.len() + active.len() > 0{
-            // check for completed steps
-            let completed: Vec<char> = active.iter()
-                .filter(|(_c, finish)| clock >= **finish)
-                .map(|(c, _finish)| c).cloned().collect();
-            for letter in completed {
-                active.remove(&letter);
-            }
+    pub fn assisted_assembly_duration(&self, workers: usize, base_delay: usize) -> Result<usize, ScheduleError> {
+        Ok(self.schedule(workers, base_delay)?.makespan)
+    }
+
+    /// Works out who does which step when, if each step has a duration
+    /// and you have some elves helping you
+    pub fn schedule(&self, workers: usize, base_delay: usize) -> Result<Schedule, ScheduleError> {
+        self.schedule_with(workers, base_delay, &Alphabetical)
+    }
 
-            // Get any tasks available to be worked on
-            let mut satisfied: Vec<char> = self.instructions.iter()
-                .filter(|(c, deps)| {
-                    pending.contains(c) &&
-                    deps.iter()
-                    .all(|dep| !pending.contains(dep) && !active.contains_key(dep))
-                })
-                .map(|(c, deps)| c.clone())
-                .collect();
-            satisfied.sort();
+    /// Works out who does which step when, using `policy` to decide
+    /// which ready steps get picked up first
+    /// 
+    /// Rather than ticking one second at a time, the clock jumps straight
+    /// to whenever the next step finishes.  Free steps go to the
+    /// lowest-numbered free worker.
+    pub fn schedule_with<P: Policy>(&self, workers: usize, base_delay: usize, policy: &P) -> Result<Schedule, ScheduleError> {
+        self.check_durations()?;
+        let context = policy.prepare(self);
+        let mut pending: HashSet<&str> = self.instructions.keys().map(|task| task.as_str()).collect();
+        let mut active: HashMap<&str, usize> = HashMap::new();
+        let mut busy: Vec<bool> = vec![false; workers];
+        let mut assignments: Vec<Assignment> = vec![];
+        let mut clock: usize = 0;
 
+        while !pending.is_empty() {
             // Give any free workers a task
+            let mut satisfied = self.ready_tasks(&pending, &active.keys().cloned().collect());
+            policy.prioritize(self, &context, &mut satisfied);
             let tasks_to_assign = cmp::min(workers - active.len(), satisfied.len());
-            for letter in satisfied.iter().take(tasks_to_assign) {
+            for task in satisfied.into_iter().take(tasks_to_assign) {
+                let worker = busy.iter().position(|b| !b).unwrap();
+                busy[worker] = true;
                 // This job will get done duration + base_delay seconds from now
-                active.insert(*letter, 
-                    DependencyGraph::duration_for(*letter) + base_delay + clock);
-                pending.remove(letter);
+                let end = self.task_duration(task)? + base_delay + clock;
+                active.insert(task, assignments.len());
+                assignments.push(Assignment { worker, task: task.to_string(), start: clock, end });
+                pending.remove(task);
             }
 
-            clock += 1;
+            if active.is_empty() {
+                if workers == 0 {
+                    return Err(ScheduleError::NoWorkers);
+                }
+                return Err(self.find_cycle(&pending));
+            }
+
+            // Skip ahead to the next completed step(s)
+            clock = active.values().map(|&i| assignments[i].end).min().unwrap();
+            active.retain(|_task, i| {
+                let done = assignments[*i].end <= clock;
+                if done {
+                    busy[assignments[*i].worker] = false;
+                }
+                !done
+            });
+        }
+
+        // Wait for the stragglers
+        let makespan = assignments.iter().map(|job| job.end).max().unwrap_or(0);
+        let (critical_path, critical_length) = self.critical_path(base_delay)?;
+        Ok(Schedule { workers, assignments, makespan, critical_path, critical_length })
+    }
+
+    /// Runs the schedule for every combination of worker count and base
+    /// delay, reporting how long each took and how busy the workers were
+    pub fn sweep(&self, workers: RangeInclusive<usize>, base_delays: &[usize]) -> Result<Vec<SweepResult>, ScheduleError> {
+        let mut results = vec![];
+        for &base_delay in base_delays {
+            for count in workers.clone() {
+                let schedule = self.schedule(count, base_delay)?;
+                results.push(SweepResult {
+                    workers: count,
+                    base_delay,
+                    makespan: schedule.makespan,
+                    utilisation: schedule.utilisation(),
+                });
+            }
+        }
+        Ok(results)
+    }
+
+    /// The fewest workers that can get everything done within `deadline`
+    /// seconds, or None if no number of workers is fast enough
+    /// 
+    /// Adding workers doesn't always make greedy scheduling faster, so
+    /// this checks every count in turn.  There's no point having more
+    /// workers than steps.
+    pub fn min_workers_for(&self, base_delay: usize, deadline: usize) -> Result<Option<usize>, ScheduleError> {
+        for count in 1..=cmp::max(1, self.instructions.len()) {
+            if self.schedule(count, base_delay)?.makespan <= deadline {
+                return Ok(Some(count));
+            }
+        }
+        Ok(None)
+    }
+
+    /// The longest chain of dependent steps, and how long it takes
+    /// 
+    /// No matter how many workers there are, it can't go any faster
+    /// than this.
+    pub fn critical_path(&self, base_delay: usize) -> Result<(Vec<String>, usize), ScheduleError> {
+        self.check_durations()?;
+        let order = self.linearize()?;
+        let mut finish: HashMap<&str, usize> = HashMap::new();
+        let mut previous: HashMap<&str, &str> = HashMap::new();
+        for task in order.iter() {
+            // A step can start once its slowest dependency is done
+            let mut start = 0;
+            for dep in self.instructions[task].iter() {
+                if !previous.contains_key(task.as_str()) || finish[dep.as_str()] > start {
+                    start = finish[dep.as_str()];
+                    previous.insert(task, dep);
+                }
+            }
+            finish.insert(task, start + self.task_duration(task)? + base_delay);
+        }
+
+        let mut current = match order.iter().max_by_key(|task| (finish[task.as_str()], cmp::Reverse(*task))) {
+            Some(task) => task.as_str(),
+            None => return Ok((vec![], 0)),
+        };
+        let length = finish[current];
+        let mut path = vec![current.to_string()];
+        while let Some(dep) = previous.get(current) {
+            current = dep;
+            path.push(current.to_string());
+        }
+        path.reverse();
+        Ok((path, length))
+    }
+
+    /// Every order the steps could legally be done in
+    /// 
+    /// The number of orders blows up fast, so this is only meant for
+    /// small graphs.
+    pub fn all_orders(&self) -> Result<Vec<Vec<String>>, ScheduleError> {
+        // Bail out early on cycles, which would have no orders at all
+        self.linearize()?;
+        let mut pending: HashSet<&str> = self.instructions.keys().map(|task| task.as_str()).collect();
+        let mut current: Vec<&str> = vec![];
+        let mut results: Vec<Vec<String>> = vec![];
+        self.extend_orders(&mut pending, &mut current, &mut results);
+        Ok(results)
+    }
+
+    /// Tries every ready step as the next one, backtracking after each
+    fn extend_orders<'a>(&'a self, pending: &mut HashSet<&'a str>, current: &mut Vec<&'a str>, results: &mut Vec<Vec<String>>) {
+        if pending.is_empty() {
+            results.push(current.iter().map(|task| task.to_string()).collect());
+            return;
+        }
+        for task in self.ready_tasks(pending, &HashSet::new()) {
+            pending.remove(task);
+            current.push(task);
+            self.extend_orders(pending, current, results);
+            current.pop();
+            pending.insert(task);
+        }
+    }
+
+    /// How many steps are waiting directly on this one
+    pub fn dependents(&self, task: &str) -> usize {
+        self.instructions.values()
+            .filter(|deps| deps.iter().any(|dep| dep == task))
+            .count()
+    }
+
+    /// For each step, the longest time it takes to get from the start
+    /// of that step to the end of everything that depends on it
+    /// 
+    /// This is only a guide for picking steps, so steps missing a
+    /// duration count as taking no time.
+    pub fn tail_lengths(&self, base_delay: usize) -> HashMap<&str, usize> {
+        let mut children: HashMap<&str, Vec<&str>> = HashMap::new();
+        for (child, deps) in self.instructions.iter() {
+            for dep in deps.iter() {
+                children.entry(dep.as_str()).or_default().push(child.as_str());
+            }
+        }
+        let mut tails: HashMap<&str, usize> = HashMap::new();
+        for task in self.instructions.keys() {
+            self.tail_length(task, base_delay, &children, &mut tails, &mut HashSet::new());
+        }
+        tails
+    }
+
+    /// Remembers tails as it goes.  Steps already on the way down
+    /// (which only happens in a cycle) count for nothing.
+    fn tail_length<'a>(&'a self, task: &'a str, base_delay: usize, children: &HashMap<&'a str, Vec<&'a str>>, tails: &mut HashMap<&'a str, usize>, visiting: &mut HashSet<&'a str>) -> usize {
+        if let Some(tail) = tails.get(task) {
+            return *tail;
+        }
+        if !visiting.insert(task) {
+            return 0;
+        }
+        let longest_after = children.get(task).into_iter()
+            .flatten()
+            .map(|child| self.tail_length(child, base_delay, children, tails, visiting))
+            .max()
+            .unwrap_or(0);
+        let tail = self.task_duration(task).unwrap_or(0) + base_delay + longest_after;
+        visiting.remove(task);
+        tails.insert(task, tail);
+        tail
+    }
+
+    /// How long a step takes, not counting the base delay
+    /// 
+    /// Durations that were set explicitly win.  Otherwise, single capital
+    /// letter steps take their place in the alphabet, and anything else
+    /// needs a duration set.
+    pub fn task_duration(&self, task: &str) -> Result<usize, ScheduleError> {
+        if let Some(duration) = self.durations.get(task) {
+            return Ok(*duration);
+        }
+        let mut chars = task.chars();
+        match (chars.next(), chars.next()) {
+            (Some(letter), None) if letter.is_ascii_uppercase() => {
+                Ok(DependencyGraph::duration_for(letter))
+            }
+            _ => Err(ScheduleError::MissingDuration(task.to_string())),
         }
+    }
 
-        // Un-tick the clock, since the clock ticks at the end.
-        clock - 1
+    /// Makes sure every step has a duration, complaining about the
+    /// first one alphabetically that doesn't
+    fn check_durations(&self) -> Result<(), ScheduleError> {
+        let mut tasks: Vec<&String> = self.instructions.keys().collect();
+        tasks.sort();
+        for task in tasks {
+            self.task_duration(task)?;
+        }
+        Ok(())
     }
 
     /// Calculates how long a letter will take to process
@@ -101,20 +613,61 @@ impl DependencyGraph {
     fn duration_for(letter: char) -> usize {
         (letter as usize) - ('A' as usize) + 1
     }
+
+    /// Steps that haven't been started whose dependencies are all
+    /// finished, sorted by name
+    fn ready_tasks<'a>(&'a self, pending: &HashSet<&str>, active: &HashSet<&str>) -> Vec<&'a str> {
+        let mut satisfied: Vec<&str> = self.instructions.iter()
+            .filter(|(task, deps)| {
+                pending.contains(task.as_str()) &&
+                deps.iter()
+                .all(|dep| !pending.contains(dep.as_str()) && !active.contains(dep.as_str()))
+            })
+            .map(|(task, _deps)| task.as_str())
+            .collect();
+        satisfied.sort();
+        satisfied
+    }
+
+    /// Finds a loop among steps that are stuck waiting on each other
+    /// 
+    /// Every stuck step is waiting on at least one other stuck step,
+    /// so walking backwards through dependencies has to come back around
+    /// eventually.  The cycle comes out in the order the steps would
+    /// have to run, starting from the alphabetically first one.
+    fn find_cycle(&self, pending: &HashSet<&str>) -> ScheduleError {
+        let mut path: Vec<&str> = vec![];
+        let mut current = *pending.iter().min().unwrap();
+        while !path.contains(&current) {
+            path.push(current);
+            current = self.instructions[current].iter()
+                .map(|dep| dep.as_str())
+                .filter(|dep| pending.contains(dep))
+                .min()
+                .unwrap();
+        }
+        let start = path.iter().position(|task| *task == current).unwrap();
+        let mut cycle: Vec<String> = path[start..].iter().map(|task| task.to_string()).collect();
+        cycle.reverse();
+        // Start from the first name alphabetically so the answer is stable
+        let first = (0..cycle.len()).min_by_key(|&i| &cycle[i]).unwrap();
+        cycle.rotate_left(first);
+        ScheduleError::Cycle(cycle)
+    }
 }
 
 /// Given lines of dependencies, processes those dependencies into a linear
 /// ordered string of instructions.
-pub fn order_steps(text: &str) -> String {
-    let deps = DependencyGraph::from_instructions(text);
-    deps.linearize().into_iter().collect()
+pub fn order_steps(text: &str) -> Result<String, ScheduleError> {
+    let deps = DependencyGraph::from_instructions(text)?;
+    Ok(deps.linearize()?.concat())
 }
 
 // Part 2: How long will it take to complete all the steps?
 
 /// Find out how long to run a set of tasks with helpers
-pub fn assisted_duration(text: &str, workers: usize, base_delay: usize) -> usize {
-    let deps = DependencyGraph::from_instructions(text);
+pub fn assisted_duration(text: &str, workers: usize, base_delay: usize) -> Result<usize, ScheduleError> {
+    let deps = DependencyGraph::from_instructions(text)?;
     deps.assisted_assembly_duration(workers, base_delay)
 }
 
@@ -122,9 +675,7 @@ pub fn assisted_duration(text: &str, workers: usize, base_delay: usize) -> usize
 mod tests {
     use super::*;
 
-    #[test]
-    fn test_part_one() {
-        let instructions = "Step C must be finished before step A can begin.
+    const EXAMPLE: &str = "Step C must be finished before step A can begin.
 Step C must be finished before step F can begin.
 Step A must be finished before step B can begin.
 Step A must be finished before step D can begin.
@@ -132,27 +683,164 @@ Step B must be finished before step E can begin.
 Step D must be finished before step E can begin.
 Step F must be finished before step E can begin.";
 
-        assert_eq!(String::from("CABDFE"), order_steps(instructions));
+    #[test]
+    fn test_part_one() {
+        assert_eq!(Ok(String::from("CABDFE")), order_steps(EXAMPLE));
     }
 
     #[test]
     fn test_part_two() {
-        let instructions = "Step C must be finished before step A can begin.
-Step C must be finished before step F can begin.
-Step A must be finished before step B can begin.
-Step A must be finished before step D can begin.
-Step B must be finished before step E can begin.
-Step D must be finished before step E can begin.
-Step F must be finished before step E can begin.";
-
-        assert_eq!(15, DependencyGraph::from_instructions(instructions)
+        assert_eq!(Ok(15), DependencyGraph::from_instructions(EXAMPLE).unwrap()
             .assisted_assembly_duration(2, 0));
     }
 
+    #[test]
+    fn test_schedule() {
+        let schedule = DependencyGraph::from_instructions(EXAMPLE).unwrap()
+            .schedule(2, 0).unwrap();
+
+        assert_eq!(15, schedule.makespan);
+        assert_eq!(vec!["C", "F", "E"], schedule.critical_path);
+        assert_eq!(14, schedule.critical_length);
+        assert_eq!(vec![(1, 0, 3), (1, 9, 15)], schedule.idle_periods());
+        assert_eq!("Worker 0 |CCCABBDDDDEEEEE|\nWorker 1 |...FFFFFF......|\n", schedule.gantt(1));
+        assert_eq!("worker,task,start,end
+0,C,0,3
+0,A,3,4
+1,F,3,9
+0,B,4,6
+0,D,6,10
+0,E,10,15
+", schedule.to_csv());
+    }
+
+    #[test]
+    fn test_policies() {
+        let deps = DependencyGraph::from_instructions(EXAMPLE).unwrap();
+
+        assert_eq!("CFADBE", deps.linearize_with(&LongestFirst).unwrap().concat());
+        assert_eq!("CABDFE", deps.linearize_with(&MostDependentsFirst).unwrap().concat());
+        assert_eq!("CFADBE", deps.linearize_with(&CriticalPathFirst { base_delay: 0 }).unwrap().concat());
+        let schedule = deps.schedule_with(2, 0, &CriticalPathFirst { base_delay: 0 }).unwrap();
+        assert_eq!(15, schedule.makespan);
+        let priorities = Priority([("F".to_string(), 5)].iter().cloned().collect());
+        assert_eq!("CFABDE", deps.linearize_with(&priorities).unwrap().concat());
+
+        let schedule = deps.schedule_with(2, 0, &LongestFirst).unwrap();
+        assert_eq!(Assignment { worker: 0, task: "F".to_string(), start: 3, end: 9 },
+            schedule.assignments[1]);
+    }
+
+    #[test]
+    fn test_all_orders() {
+        let orders = DependencyGraph::from_instructions(EXAMPLE).unwrap().all_orders().unwrap();
+
+        assert_eq!(8, orders.len());
+        assert_eq!("CABDFE", orders[0].concat());
+        assert!(orders.iter().all(|order| order[0] == "C" && order[5] == "E"));
+    }
+
+    #[test]
+    fn test_worker_sweep() {
+        let deps = DependencyGraph::from_instructions(EXAMPLE).unwrap();
+        let results = deps.sweep(1..=3, &[0, 60]).unwrap();
+        let makespans: Vec<(usize, usize, usize)> = results.iter()
+            .map(|result| (result.workers, result.base_delay, result.makespan))
+            .collect();
+
+        assert_eq!(vec![(1, 0, 21), (2, 0, 15), (3, 0, 14), (1, 60, 381), (2, 60, 258), (3, 60, 253)], makespans);
+        assert_eq!(1.0, results[0].utilisation);
+        assert_eq!(21.0 / 30.0, results[1].utilisation);
+
+        assert_eq!(Ok(Some(2)), deps.min_workers_for(0, 15));
+        assert_eq!(Ok(Some(3)), deps.min_workers_for(0, 14));
+        assert_eq!(Ok(None), deps.min_workers_for(0, 13));
+    }
+
+    #[test]
+    fn test_named_tasks_and_durations() {
+        let instructions = "Step fetch must be finished before step build can begin.
+Step build must be finished before step test can begin.
+Step lint must be finished before step test can begin.
+Step fetch takes 5 seconds.";
+        let mut deps = DependencyGraph::from_instructions(instructions).unwrap();
+        deps.load_durations("build 10\nlint 3\ntest 2").unwrap();
+
+        assert_eq!(vec!["fetch", "build", "lint", "test"], deps.linearize().unwrap());
+        assert_eq!(Ok(17), deps.assisted_assembly_duration(2, 0));
+        assert_eq!(Ok(20), deps.assisted_assembly_duration(1, 0));
+
+        let schedule = deps.schedule(2, 0).unwrap();
+        assert_eq!("Worker 0 |AAAAACCCCCCCCCCDD|
+Worker 1 |BBB..............|
+A = fetch
+B = lint
+C = build
+D = test
+", schedule.gantt(1));
+        // lint only takes 3 seconds, but still shows up in a 4 second column
+        assert_eq!("Worker 0 |ACCCD|
+Worker 1 |B....|
+A = fetch
+B = lint
+C = build
+D = test
+", schedule.gantt(4));
+    }
+
+    #[test]
+    fn test_cycle() {
+        let instructions = "Step A must be finished before step B can begin.
+Step B must be finished before step C can begin.
+Step C must be finished before step D can begin.
+Step D must be finished before step B can begin.";
+        let cycle = ScheduleError::Cycle(vec!["B".to_string(), "C".to_string(), "D".to_string()]);
+
+        assert_eq!(Err(cycle), order_steps(instructions));
+        assert!(assisted_duration(instructions, 3, 0).is_err());
+    }
+
     #[test]
     fn test_duration() {
         assert_eq!(1, DependencyGraph::duration_for('A'));
         assert_eq!(26, DependencyGraph::duration_for('Z'));
         assert_eq!(10, DependencyGraph::duration_for('J'));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_parse_errors() {
+        let instructions = "Step A must be finished before step B can begin.
+
+Step B needs doing.";
+        assert_eq!(Err(ParseError::UnknownInstruction { line: 3, text: "Step B needs doing.".to_string() }),
+            DependencyGraph::from_instructions(instructions).map(|_deps| ()));
+        assert_eq!(Err(ParseError::BadDuration { line: 1, token: "ten".to_string() }),
+            DependencyGraph::from_instructions("Step A takes ten seconds.").map(|_deps| ()));
+        assert_eq!(Err(ScheduleError::Parse(ParseError::UnknownInstruction { line: 1, text: "nonsense".to_string() })),
+            order_steps("nonsense"));
+
+        let mut deps = DependencyGraph::new();
+        assert_eq!(Err(ParseError::MissingDuration { line: 2 }), deps.load_durations("build 10\nlint\ntest 2"));
+        assert_eq!(Err(ParseError::BadDuration { line: 1, token: "-3".to_string() }), deps.load_durations("lint -3"));
+        assert_eq!(Ok(()), deps.load_durations("\nlint 3\n"));
+    }
+
+    #[test]
+    fn test_missing_duration() {
+        let instructions = "Step foo must be finished before step bar can begin.
+Step foo takes 4 seconds.";
+        let deps = DependencyGraph::from_instructions(instructions).unwrap();
+
+        assert_eq!(Err(ScheduleError::MissingDuration("bar".to_string())), deps.task_duration("bar"));
+        assert_eq!(Err(ScheduleError::MissingDuration("bar".to_string())), deps.assisted_assembly_duration(2, 0));
+        assert!(deps.critical_path(0).is_err());
+        assert_eq!(vec!["foo", "bar"], deps.linearize_with(&LongestFirst).unwrap());
+    }
+
+    #[test]
+    #[should_panic(expected = "at least one second")]
+    fn test_gantt_zero_columns() {
+        let deps = DependencyGraph::from_instructions("Step A must be finished before step B can begin.").unwrap();
+        deps.schedule(1, 0).unwrap().gantt(0);
+    }
+}
@@ -2,58 +2,366 @@
 /// 
 /// Build a license tree!
 
+use std::cell::OnceCell;
+use std::mem;
+use std::rc::Rc;
+
+/// Ways that a license file can fail to make a tree
+#[derive(Debug, PartialEq)]
+pub enum ParseError {
+    /// The numbers ran out partway through a node.  `position` is the
+    /// index of the number that should have been there.
+    Truncated { position: usize },
+    /// Something that isn't a number showed up at `position`
+    BadNumber { position: usize, token: String },
+    /// The tree was finished but there were still numbers left,
+    /// starting at `position`
+    TrailingData { position: usize },
+    /// JSON that doesn't describe a tree.  `offset` is the byte where
+    /// things went wrong.
+    BadJson { offset: usize, expected: &'static str },
+}
+
 /// A node in a GPS Licensing tree structure
+/// 
+/// value_cache remembers the node's value once it's been worked out,
+/// so asking again (or asking a parent) doesn't redo the whole subtree
 pub struct Node {
     metadata: Vec<usize>,
-    children: Vec<Box<Node>>,
+    children: Vec<Node>,
+    value_cache: OnceCell<usize>,
+}
+
+/// A node seen while walking a tree, along with how deep it is and
+/// the child indices that lead to it from where the walk started
+/// 
+/// The path is shared with the walk and only copied if the walk moves
+/// on while this Visit is still around, so walking a tree is linear
+/// even when it's very deep.
+pub struct Visit<'a> {
+    pub node: &'a Node,
+    pub depth: usize,
+    path: Rc<Vec<usize>>,
+}
+
+impl<'a> Visit<'a> {
+    /// The child indices leading to this node
+    pub fn path(&self) -> &[usize] {
+        &self.path
+    }
+}
+
+/// Walks a tree parents-first
+/// 
+/// stack holds the nodes still to visit with their depth and which
+/// child of their parent they are.  path is the path to the node
+/// visited last.
+pub struct PreOrder<'a> {
+    stack: Vec<(&'a Node, usize, usize)>,
+    path: Rc<Vec<usize>>,
+}
+
+impl<'a> Iterator for PreOrder<'a> {
+    type Item = Visit<'a>;
+
+    fn next(&mut self) -> Option<Visit<'a>> {
+        let (node, depth, index) = self.stack.pop()?;
+        {
+            let path = Rc::make_mut(&mut self.path);
+            if depth > 0 {
+                path.truncate(depth - 1);
+                path.push(index);
+            }
+        }
+        // Reversed so that the first child comes out first
+        for (i, child) in node.children.iter().enumerate().rev() {
+            self.stack.push((child, depth + 1, i));
+        }
+        Some(Visit { node, depth, path: Rc::clone(&self.path) })
+    }
+}
+
+/// Walks a tree children-first
+/// 
+/// stack runs from the root down to the node being worked on, each
+/// with the next of its children to go into.  path matches the stack,
+/// except that the last index is left on after a node is visited and
+/// only taken off on the next step, so the Visit can share it.
+pub struct PostOrder<'a> {
+    stack: Vec<(&'a Node, usize)>,
+    path: Rc<Vec<usize>>,
+    leaving: bool,
+}
+
+impl<'a> Iterator for PostOrder<'a> {
+    type Item = Visit<'a>;
+
+    fn next(&mut self) -> Option<Visit<'a>> {
+        if self.leaving {
+            Rc::make_mut(&mut self.path).pop();
+            self.leaving = false;
+        }
+        loop {
+            let (node, next_child) = self.stack.last_mut()?;
+            let node: &'a Node = node;
+            if *next_child < node.children.len() {
+                let i = *next_child;
+                *next_child += 1;
+                Rc::make_mut(&mut self.path).push(i);
+                self.stack.push((&node.children[i], 0));
+            } else {
+                self.stack.pop();
+                self.leaving = !self.stack.is_empty();
+                return Some(Visit { node, depth: self.stack.len(), path: Rc::clone(&self.path) });
+            }
+        }
+    }
+}
+
+/// A node that's still being read: how many more children it's
+/// waiting on and how many metadata entries follow them
+struct PartialNode {
+    node: Node,
+    children_left: usize,
+    metadata_count: usize,
+}
+
+/// Hands out numbers from the license text one at a time, keeping track
+/// of where it is so errors can say where things went wrong
+struct Tokens<'a> {
+    words: std::str::SplitWhitespace<'a>,
+    position: usize,
+}
+
+impl<'a> Tokens<'a> {
+    fn new(text: &'a str) -> Self {
+        Self { words: text.split_whitespace(), position: 0 }
+    }
+
+    fn next_number(&mut self) -> Result<usize, ParseError> {
+        let position = self.position;
+        let word = self.words.next().ok_or(ParseError::Truncated { position })?;
+        self.position += 1;
+        word.parse().map_err(|_| ParseError::BadNumber { position, token: word.to_string() })
+    }
+
+    fn header(&mut self) -> Result<PartialNode, ParseError> {
+        let children_left = self.next_number()?;
+        let metadata_count = self.next_number()?;
+        Ok(PartialNode { node: Node::new(), children_left, metadata_count })
+    }
 }
 
 impl Node {
     pub fn new() -> Self {
-        Self { metadata: vec![], children: vec![] }
+        Self { metadata: vec![], children: vec![], value_cache: OnceCell::new() }
+    }
+
+    /// Builder-style: adds a child after any existing children
+    pub fn with_child(mut self, child: Node) -> Self {
+        self.children.push(child);
+        self.value_cache = OnceCell::new();
+        self
+    }
+
+    /// Builder-style: adds metadata entries after any existing ones
+    pub fn with_metadata(mut self, entries: &[usize]) -> Self {
+        self.metadata.extend(entries);
+        self.value_cache = OnceCell::new();
+        self
+    }
+
+    pub fn metadata(&self) -> &[usize] {
+        &self.metadata
+    }
+
+    pub fn children(&self) -> &[Node] {
+        &self.children
+    }
+
+    /// Finds the node at the end of a path of child indices (starting
+    /// from 0), or None if the path leads off the tree
+    pub fn get(&self, path: &[usize]) -> Option<&Node> {
+        let mut node = self;
+        for &i in path {
+            node = node.children.get(i)?;
+        }
+        Some(node)
+    }
+
+    /// Every node from here down, each one before its children
+    pub fn pre_order(&self) -> PreOrder<'_> {
+        PreOrder { stack: vec![(self, 0, 0)], path: Rc::new(vec![]) }
+    }
+
+    /// Every node from here down, each one after its children
+    pub fn post_order(&self) -> PostOrder<'_> {
+        PostOrder { stack: vec![(self, 0)], path: Rc::new(vec![]), leaving: false }
     }
 
-    /// Generates a node from a string of space-separated integers
-    pub fn from_text(text: &str) -> Self {
-        let data: Vec<usize> = text.split(' ').map(|num|{
-            num.parse().unwrap()
-        }).collect();
-        let (node, _ptr) = Node::build_child(&data, 0);
-        node
+    /// Draws the tree one node per line, showing each node's metadata
+    /// and value
+    /// 
+    /// ```text
+    /// metadata [1, 1, 2], value 66
+    /// |-- metadata [10, 11, 12], value 33
+    /// `-- metadata [2], value 0
+    ///     `-- metadata [99], value 99
+    /// ```
+    pub fn pretty(&self) -> String {
+        let mut result = String::new();
+        // Each entry: node, the indent leading up to it, and its connector
+        let mut stack: Vec<(&Node, String, &str)> = vec![(self, String::new(), "")];
+        while let Some((node, indent, connector)) = stack.pop() {
+            result.push_str(&format!("{}{}metadata {:?}, value {}\n",
+                indent, connector, node.metadata, node.value()));
+            let child_indent = match connector {
+                "|-- " => format!("{}|   ", indent),
+                "`-- " => format!("{}    ", indent),
+                _ => indent,
+            };
+            for (i, child) in node.children.iter().enumerate().rev() {
+                let connector = if i + 1 == node.children.len() { "`-- " } else { "|-- " };
+                stack.push((child, child_indent.clone(), connector));
+            }
+        }
+        result
     }
 
-    /// Builds a child based on a strand of data and a pointer to start at.
+    /// Generates a node from a string of whitespace-separated integers
     /// 
-    /// These nodes are recursive in their layout.  So, for example, 
+    /// These nodes are recursive in their layout.  So, for example,
     /// the root node has a header at the start of the string, and its
-    /// metadata comes after all of the rest of the nodes in the tree
-    fn build_child(data: &Vec<usize>, start: usize) -> (Node, usize) {
-        let mut result = Node::new();
-        let mut ptr = start;
-        let children = data[ptr];
-        ptr += 1;
-        let metadata = data[ptr];
-        ptr += 1;
+    /// metadata comes after all of the rest of the nodes in the tree.
+    /// Rather than recursing, this keeps its own stack of the nodes
+    /// that are partway built, so really deep trees are fine.
+    pub fn from_text(text: &str) -> Result<Self, ParseError> {
+        let mut tokens = Tokens::new(text);
+        let mut stack: Vec<PartialNode> = vec![tokens.header()?];
+
+        loop {
+            let top = stack.last_mut().unwrap();
+            if top.children_left > 0 {
+                top.children_left -= 1;
+                let child = tokens.header()?;
+                stack.push(child);
+                continue;
+            }
+
+            for _i in 0..top.metadata_count {
+                let entry = tokens.next_number()?;
+                top.node.metadata.push(entry);
+            }
+            let finished = stack.pop().unwrap().node;
+            match stack.last_mut() {
+                Some(parent) => parent.node.children.push(finished),
+                None => {
+                    if tokens.words.next().is_some() {
+                        return Err(ParseError::TrailingData { position: tokens.position });
+                    }
+                    return Ok(finished);
+                }
+            }
+        }
+    }
+
+    /// Writes the tree back out in the same format `from_text` reads:
+    /// a header of child count and metadata count, then the children,
+    /// then the metadata
+    pub fn to_text(&self) -> String {
+        let mut numbers: Vec<String> = vec![];
+        self.walk(|node, entering| {
+            if entering {
+                numbers.push(node.children.len().to_string());
+                numbers.push(node.metadata.len().to_string());
+            } else {
+                numbers.extend(node.metadata.iter().map(|entry| entry.to_string()));
+            }
+        });
+        numbers.join(" ")
+    }
+
+    /// Writes the tree as JSON, with each node looking like
+    /// `{"metadata":[1,2],"children":[...]}`
+    pub fn to_json(&self) -> String {
+        let mut result = String::new();
+        let mut just_left = false;
+        self.walk(|node, entering| {
+            if entering {
+                // Coming right after a sibling needs a separator
+                if just_left { result.push(','); }
+                let metadata: Vec<String> = node.metadata.iter().map(|entry| entry.to_string()).collect();
+                result.push_str(&format!("{{\"metadata\":[{}],\"children\":[", metadata.join(",")));
+            } else {
+                result.push_str("]}");
+            }
+            just_left = !entering;
+        });
+        result
+    }
+
+    /// Reads a tree from the JSON that `to_json` writes
+    /// 
+    /// Keys can come in either order and either can be left out.  Like
+    /// `from_text`, this keeps its own stack instead of recursing.
+    pub fn from_json(text: &str) -> Result<Self, ParseError> {
+        let mut json = Json::new(text);
+        let mut stack: Vec<Node> = vec![];
+        let mut current = json.open_object()?;
+        let mut first_member = true;
 
-        // Generate and add children
-        for _i in 0..children {
-            let (node, new_ptr) = Node::build_child(&data,ptr);
-            result.children.push(Box::new(node));
-            ptr = new_ptr;
+        loop {
+            if json.next_member(&mut current, first_member)? {
+                // Opened up a non-empty children list, so go read the first child
+                stack.push(current);
+                current = json.open_object()?;
+                first_member = true;
+                continue;
+            }
+
+            // `current` is finished, so hand it to its parent
+            let mut parent = match stack.pop() {
+                Some(parent) => parent,
+                None => {
+                    json.finish()?;
+                    return Ok(current);
+                }
+            };
+            parent.children.push(current);
+            if json.eat(b',') {
+                stack.push(parent);
+                current = json.open_object()?;
+                first_member = true;
+            } else {
+                json.expect(b']', "',' or ']' after a child")?;
+                current = parent;
+                first_member = false;
+            }
         }
-        
-        result.metadata.extend(&data[ptr..(ptr+metadata)]);
-        ptr += metadata;
+    }
 
-        (result, ptr)
+    /// Visits every node in order, once on the way in (before its
+    /// children) and once on the way out (after them)
+    fn walk<F: FnMut(&Node, bool)>(&self, mut visit: F) {
+        let mut stack: Vec<(&Node, bool)> = vec![(self, true)];
+        while let Some((node, entering)) = stack.pop() {
+            visit(node, entering);
+            if entering {
+                stack.push((node, false));
+                stack.extend(node.children.iter().rev().map(|child| (child, true)));
+            }
+        }
     }
 
-    /// Calculate the recurive total of all the metadata here and below
+    /// Calculate the total of all the metadata here and below
     pub fn metadata_total(&self) -> usize {
-        let my_metadata: usize = self.metadata.iter().sum();
-        let children_total: usize = self.children.iter()
-            .map(|child| child.metadata_total()).sum();
-        my_metadata + children_total
+        let mut total = 0;
+        let mut stack: Vec<&Node> = vec![self];
+        while let Some(node) = stack.pop() {
+            total += node.metadata.iter().sum::<usize>();
+            stack.extend(node.children.iter());
+        }
+        total
     }
 
     /// Calculates a node's value.
@@ -64,18 +372,154 @@ impl Node {
     ///    and each metadata is a pointer at a particular child.
     ///    This node's value is the sum of *those* nodes' values.
     ///    If a pointer is invalid, skip it.
+    /// 
+    /// Children get worked out before their parents using a stack,
+    /// with finished values piling up on a second stack.  Every value
+    /// gets cached on its node, and cached nodes aren't revisited.
     pub fn value(&self) -> usize {
-        if self.children.is_empty() { return self.metadata.iter().sum(); }
+        let mut pending: Vec<(&Node, bool)> = vec![(self, false)];
+        let mut values: Vec<usize> = vec![];
+        while let Some((node, children_done)) = pending.pop() {
+            if let Some(value) = node.value_cache.get() {
+                values.push(*value);
+                continue;
+            }
+            if !children_done {
+                pending.push((node, true));
+                // Reversed so that the first child finishes first
+                pending.extend(node.children.iter().rev().map(|child| (child, false)));
+                continue;
+            }
 
-        let mut total: usize = 0;
-        for pointer in self.metadata.iter() {
-            if *pointer < 1 || *pointer > self.children.len() { continue; }
+            let total = if node.children.is_empty() {
+                node.metadata.iter().sum()
+            } else {
+                let child_values = values.split_off(values.len() - node.children.len());
+                node.metadata.iter()
+                    .filter(|pointer| **pointer >= 1 && **pointer <= child_values.len())
+                    .map(|pointer| child_values[*pointer - 1])
+                    .sum()
+            };
+            node.value_cache.set(total).unwrap();
+            values.push(total);
+        }
+        values[0]
+    }
+}
+
+/// Reads through just enough JSON to pull out license trees
+struct Json<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
 
-            total += self.children.get(*pointer - 1)
-                .expect("Couldn't get child value")
-                .value();
+impl<'a> Json<'a> {
+    fn new(text: &'a str) -> Self {
+        Self { bytes: text.as_bytes(), offset: 0 }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.offset < self.bytes.len() && self.bytes[self.offset].is_ascii_whitespace() {
+            self.offset += 1;
+        }
+    }
+
+    fn error<T>(&self, expected: &'static str) -> Result<T, ParseError> {
+        Err(ParseError::BadJson { offset: self.offset, expected })
+    }
+
+    /// Consumes `byte` if it's next, reporting whether it was there
+    fn eat(&mut self, byte: u8) -> bool {
+        self.skip_whitespace();
+        if self.bytes.get(self.offset) == Some(&byte) {
+            self.offset += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, byte: u8, expected: &'static str) -> Result<(), ParseError> {
+        if self.eat(byte) { Ok(()) } else { self.error(expected) }
+    }
+
+    fn open_object(&mut self) -> Result<Node, ParseError> {
+        self.expect(b'{', "'{' to start a node")?;
+        Ok(Node::new())
+    }
+
+    /// Reads the next key and its value into `node`
+    /// 
+    /// Returns true if it just opened a children list with a child in
+    /// it, and false if the node's closing brace was reached.
+    fn next_member(&mut self, node: &mut Node, mut first: bool) -> Result<bool, ParseError> {
+        loop {
+            if self.eat(b'}') {
+                return Ok(false);
+            }
+            if !first {
+                self.expect(b',', "',' or '}' after a value")?;
+            }
+            first = false;
+
+            let key = self.key()?;
+            self.expect(b':', "':' after a key")?;
+            self.expect(b'[', "'[' to start a list")?;
+            if key == "children" {
+                if self.eat(b']') { continue; }
+                return Ok(true);
+            }
+            if self.eat(b']') { continue; }
+            loop {
+                let entry = self.number()?;
+                node.metadata.push(entry);
+                if self.eat(b']') { break; }
+                self.expect(b',', "',' or ']' after a number")?;
+            }
+        }
+    }
+
+    fn key(&mut self) -> Result<&'static str, ParseError> {
+        self.skip_whitespace();
+        for key in ["metadata", "children"].iter() {
+            let quoted = format!("\"{}\"", key);
+            if self.bytes[self.offset..].starts_with(quoted.as_bytes()) {
+                self.offset += quoted.len();
+                return Ok(key);
+            }
+        }
+        self.error("\"metadata\" or \"children\"")
+    }
+
+    fn number(&mut self) -> Result<usize, ParseError> {
+        self.skip_whitespace();
+        let start = self.offset;
+        while self.offset < self.bytes.len() && self.bytes[self.offset].is_ascii_digit() {
+            self.offset += 1;
+        }
+        match std::str::from_utf8(&self.bytes[start..self.offset]).unwrap().parse() {
+            Ok(number) => Ok(number),
+            Err(_) => {
+                self.offset = start;
+                self.error("a number")
+            }
+        }
+    }
+
+    fn finish(&mut self) -> Result<(), ParseError> {
+        self.skip_whitespace();
+        if self.offset < self.bytes.len() { self.error("nothing after the tree") } else { Ok(()) }
+    }
+}
+
+/// Tears trees down one node at a time instead of recursively, so
+/// deep trees don't blow the stack on the way out either
+impl Drop for Node {
+    fn drop(&mut self) {
+        let mut stack: Vec<Node> = mem::take(&mut self.children);
+        while let Some(mut node) = stack.pop() {
+            stack.append(&mut node.children);
         }
-        total
     }
 }
 
@@ -86,12 +530,122 @@ mod tests {
     #[test]
     fn test_part_one() {
         let license = "2 3 0 3 10 11 12 1 1 0 1 99 2 1 1 2";
-        assert_eq!(138, Node::from_text(license).metadata_total());
+        assert_eq!(138, Node::from_text(license).unwrap().metadata_total());
     }
 
     #[test]
     fn test_part_two() {
         let license = "2 3 0 3 10 11 12 1 1 0 1 99 2 1 1 2";
-        assert_eq!(66, Node::from_text(license).value());
+        assert_eq!(66, Node::from_text(license).unwrap().value());
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_messy_whitespace() {
+        let license = "2 3 0 3  10 11 12\n1 1 0 1 99 2 1 1 2\n";
+        assert_eq!(138, Node::from_text(license).unwrap().metadata_total());
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(Some(ParseError::Truncated { position: 15 }),
+            Node::from_text("2 3 0 3 10 11 12 1 1 0 1 99 2 1 1").err());
+        assert_eq!(Some(ParseError::BadNumber { position: 4, token: "ten".to_string() }),
+            Node::from_text("2 3 0 3 ten 11 12 1 1 0 1 99 2 1 1 2").err());
+        assert_eq!(Some(ParseError::TrailingData { position: 16 }),
+            Node::from_text("2 3 0 3 10 11 12 1 1 0 1 99 2 1 1 2 7").err());
+    }
+
+    #[test]
+    fn test_builder_and_text_round_trip() {
+        let license = "2 3 0 3 10 11 12 1 1 0 1 99 2 1 1 2";
+        let built = Node::new()
+            .with_child(Node::new().with_metadata(&[10, 11, 12]))
+            .with_child(Node::new()
+                .with_child(Node::new().with_metadata(&[99]))
+                .with_metadata(&[2]))
+            .with_metadata(&[1, 1, 2]);
+
+        assert_eq!(license, built.to_text());
+        assert_eq!(license, Node::from_text(license).unwrap().to_text());
+    }
+
+    #[test]
+    fn test_json_round_trip() {
+        let tree = Node::new()
+            .with_child(Node::new().with_metadata(&[10, 11]))
+            .with_child(Node::new())
+            .with_metadata(&[1, 2]);
+        let json = r#"{"metadata":[1,2],"children":[{"metadata":[10,11],"children":[]},{"metadata":[],"children":[]}]}"#;
+
+        assert_eq!(json, tree.to_json());
+        assert_eq!(tree.to_text(), Node::from_json(json).unwrap().to_text());
+
+        let reordered = r#"{ "children": [ {"metadata": [10, 11]}, {} ], "metadata": [1, 2] }"#;
+        assert_eq!(tree.to_text(), Node::from_json(reordered).unwrap().to_text());
+
+        let license = "2 3 0 3 10 11 12 1 1 0 1 99 2 1 1 2";
+        let parsed = Node::from_text(license).unwrap();
+        assert_eq!(license, Node::from_json(&parsed.to_json()).unwrap().to_text());
+    }
+
+    #[test]
+    fn test_bad_json() {
+        assert_eq!(Some(ParseError::BadJson { offset: 13, expected: "a number" }),
+            Node::from_json(r#"{"metadata":[x]}"#).err());
+        assert_eq!(Some(ParseError::BadJson { offset: 1, expected: "\"metadata\" or \"children\"" }),
+            Node::from_json(r#"{"value":[]}"#).err());
+    }
+
+    #[test]
+    fn test_traversals() {
+        let tree = Node::from_text("2 3 0 3 10 11 12 1 1 0 1 99 2 1 1 2").unwrap();
+        let pre: Vec<(usize, Vec<usize>)> = tree.pre_order()
+            .map(|visit| (visit.depth, visit.path().to_vec()))
+            .collect();
+        let post: Vec<Vec<usize>> = tree.post_order().map(|visit| visit.path().to_vec()).collect();
+
+        assert_eq!(vec![(0, vec![]), (1, vec![0]), (1, vec![1]), (2, vec![1, 0])], pre);
+        assert_eq!(vec![vec![0], vec![1, 0], vec![1], vec![]], post);
+        assert_eq!(&[99], tree.get(&[1, 0]).unwrap().metadata());
+        assert!(tree.get(&[0, 0]).is_none());
+        assert_eq!(2, tree.children().len());
+    }
+
+    #[test]
+    fn test_cached_values() {
+        let tree = Node::from_text("2 3 0 3 10 11 12 1 1 0 1 99 2 1 1 2").unwrap();
+        assert_eq!(99, tree.get(&[1, 0]).unwrap().value());
+        assert_eq!(66, tree.value());
+        assert_eq!(66, tree.value());
+        let values: Vec<usize> = tree.post_order().map(|visit| visit.node.value()).collect();
+        assert_eq!(vec![33, 99, 0, 66], values);
+    }
+
+    #[test]
+    fn test_pretty() {
+        let tree = Node::from_text("2 3 0 3 10 11 12 1 1 0 1 99 2 1 1 2").unwrap();
+        let expected = "metadata [1, 1, 2], value 66
+|-- metadata [10, 11, 12], value 33
+`-- metadata [2], value 0
+    `-- metadata [99], value 99
+";
+        assert_eq!(expected, tree.pretty());
+    }
+
+    #[test]
+    fn test_deep_tree() {
+        let depth = 200_000;
+        let mut license = "1 1 ".repeat(depth);
+        license.push_str("0 1 5 ");
+        license.push_str(&"1 ".repeat(depth));
+
+        let tree = Node::from_text(&license).unwrap();
+        assert_eq!(5 + depth, tree.metadata_total());
+        assert_eq!(5, tree.value());
+        assert_eq!(license.trim(), tree.to_text());
+        assert_eq!(license.trim(), Node::from_json(&tree.to_json()).unwrap().to_text());
+        assert_eq!(depth + 1, tree.pre_order().count());
+        assert_eq!(depth, tree.post_order().next().unwrap().depth);
+        assert_eq!(depth + 1, tree.post_order().count());
+    }
+}
@@ -2,50 +2,312 @@
 /// 
 /// Figure out the scores of elves playing marbles
 
-use std::collections::VecDeque;
+/// How many marbles to play between calls to a progress callback
+const PROGRESS_INTERVAL: usize = 100_000;
 
-/// Calculate what the highest player's score is
-pub fn winning_score(number_of_players: usize, max_points: usize) -> usize {
-    let mut marbles: VecDeque<usize> = VecDeque::new();
-    let mut players: Vec<usize> = Vec::new();
-    for _i in 0..number_of_players {
-        players.push(0);
-    }
-
-    marbles.push_back(0);
-    marbles.push_back(1);
-
-    for i in 2..=max_points {
-        if i % 100000 == 0 { println!("{}", i) };
-        
-        if i % 23 == 0 {
-            players[i % number_of_players] += i;
-            rotate_right(&mut marbles, 7);
-            players[i % number_of_players] += marbles.pop_back().unwrap();
-            rotate_left(&mut marbles, 1);
-        } else {
-            rotate_left(&mut marbles, 1);
-            marbles.push_back(i);
+/// A circle of marbles stored as a doubly linked list, where every
+/// marble's neighbors are looked up by the marble's own number
+/// 
+/// Inserting, removing, and stepping around the circle are all O(1),
+/// with no shuffling of elements.  Once every marble has been taken
+/// out, `current` doesn't mean anything until a marble goes back in.
+struct Circle {
+    clockwise: Vec<usize>,
+    counter_clockwise: Vec<usize>,
+    in_circle: Vec<bool>,
+    len: usize,
+    current: usize,
+}
+
+impl Circle {
+    /// Starts a circle with `starting` marbles placed clockwise in
+    /// order, with room for marbles up to `max_marble`
+    /// 
+    /// The last starting marble is the current one.
+    fn new(starting: &[usize], max_marble: usize) -> Self {
+        let size = starting.iter().cloned().chain(Some(max_marble)).max().unwrap() + 1;
+        let mut circle = Self {
+            clockwise: vec![0; size],
+            counter_clockwise: vec![0; size],
+            in_circle: vec![false; size],
+            len: 0,
+            current: 0,
+        };
+        for &marble in starting.iter() {
+            circle.insert(marble);
         }
+        circle
     }
 
-    *players.iter().max().unwrap()
-}
+    /// Moves the current marble `steps` marbles clockwise
+    fn move_clockwise(&mut self, steps: usize) {
+        if self.len == 0 {
+            return;
+        }
+        for _i in 0..steps {
+            self.current = self.clockwise[self.current];
+        }
+    }
 
-fn rotate_right(list: &mut VecDeque<usize>, times: usize) {
-    for _i in 0..times {
-        let value = list.pop_back().expect("Empty VecDeque");
-        list.push_front(value);
+    /// Moves the current marble `steps` marbles counter-clockwise
+    fn move_counter_clockwise(&mut self, steps: usize) {
+        if self.len == 0 {
+            return;
+        }
+        for _i in 0..steps {
+            self.current = self.counter_clockwise[self.current];
+        }
     }
+
+    /// Puts a marble just clockwise of the current one and makes it current
+    fn insert(&mut self, marble: usize) {
+        if self.len == 0 {
+            self.clockwise[marble] = marble;
+            self.counter_clockwise[marble] = marble;
+            self.in_circle[marble] = true;
+            self.len = 1;
+            self.current = marble;
+            return;
+        }
+        let before = self.current;
+        let after = self.clockwise[before];
+        self.clockwise[before] = marble;
+        self.counter_clockwise[marble] = before;
+        self.clockwise[marble] = after;
+        self.counter_clockwise[after] = marble;
+        self.in_circle[marble] = true;
+        self.len += 1;
+        self.current = marble;
+    }
+
+    /// Lists the marbles clockwise, starting from the lowest-numbered
+    /// marble still in the circle
+    fn to_vec(&self) -> Vec<usize> {
+        let first = match self.in_circle.iter().position(|&present| present) {
+            Some(first) => first,
+            None => return vec![],
+        };
+        let mut result = vec![first];
+        let mut marble = self.clockwise[first];
+        while marble != first {
+            result.push(marble);
+            marble = self.clockwise[marble];
+        }
+        result
+    }
+
+    /// Takes out the current marble, making the one clockwise of it
+    /// current.  There's nothing to take if the circle is empty.
+    fn remove(&mut self) -> Option<usize> {
+        if self.len == 0 {
+            return None;
+        }
+        let marble = self.current;
+        let before = self.counter_clockwise[marble];
+        let after = self.clockwise[marble];
+        self.clockwise[before] = after;
+        self.counter_clockwise[after] = before;
+        self.in_circle[marble] = false;
+        self.len -= 1;
+        self.current = after;
+        Some(marble)
+    }
+}
+
+/// One time a player got to keep marbles
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScoringTurn {
+    /// The marble that would have been placed (a multiple of 23)
+    pub marble: usize,
+    /// The marble taken out of the circle
+    pub removed: usize,
+    /// Who scored, counting from 0 for the player who places the first marble
+    pub player: usize,
+}
+
+/// Everything that happened in a game
+/// 
+/// scores[0] is the player who placed the first marble (normally
+/// marble 1), scores[1] placed the second, and so on.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GameRecord {
+    pub scores: Vec<usize>,
+    pub winner: usize,
+    pub scoring_turns: Vec<ScoringTurn>,
+}
+
+/// A snapshot of the circle: the marbles in clockwise order starting
+/// from the lowest-numbered one (normally 0), and which one is current
+#[derive(Debug, Clone, PartialEq)]
+pub struct CircleState {
+    pub marbles: Vec<usize>,
+    pub current: usize,
 }
 
-fn rotate_left(list: &mut VecDeque<usize>, times: usize) {
-    for _i in 0..times {
-        let value = list.pop_front().expect("Empty VecDeque");
-        list.push_back(value);
+/// Why a set of `Rules` can't be played
+#[derive(Debug, Clone, PartialEq)]
+pub enum RulesError {
+    /// There has to be at least one player
+    NoPlayers,
+    /// There has to be at least one marble in the circle to start
+    NoStartingMarbles,
+    /// A marble can only be in the circle once
+    DuplicateStartingMarble(usize),
+    /// With a scoring interval of 1 every marble scores, so nothing
+    /// is ever placed and the circle runs out
+    ScoringEveryMarble,
+}
+
+/// Everything that can be tweaked about how the game is played
+/// 
+/// Not every combination makes sense, so `validate` checks them, and
+/// playing with rules that don't pass gives back the problem instead.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rules {
+    pub players: usize,
+    /// The last marble to get played
+    pub last_marble: usize,
+    /// Marbles that are a multiple of this score instead of being
+    /// placed.  0 means nothing ever scores.
+    pub scoring_interval: usize,
+    /// How many marbles counter-clockwise to go to find the one that
+    /// gets taken when scoring
+    pub removal_offset: usize,
+    /// How many marbles clockwise to go before placing a new marble
+    /// just clockwise of that one
+    pub insert_offset: usize,
+    /// The marbles in the circle before anyone plays, in clockwise
+    /// order.  The last one starts as the current marble, and play
+    /// picks up with the next number after the biggest one.
+    pub starting_marbles: Vec<usize>,
+}
+
+impl Rules {
+    /// The puzzle's rules: every 23rd marble scores, taking the marble
+    /// 7 counter-clockwise, and other marbles go between the marbles 1
+    /// and 2 clockwise.  Play starts with just marble 0.
+    pub fn new(players: usize, last_marble: usize) -> Self {
+        Self {
+            players,
+            last_marble,
+            scoring_interval: 23,
+            removal_offset: 7,
+            insert_offset: 1,
+            starting_marbles: vec![0],
+        }
+    }
+
+    /// Checks that a game can be played with these rules
+    pub fn validate(&self) -> Result<(), RulesError> {
+        if self.players == 0 {
+            return Err(RulesError::NoPlayers);
+        }
+        if self.starting_marbles.is_empty() {
+            return Err(RulesError::NoStartingMarbles);
+        }
+        let mut sorted = self.starting_marbles.clone();
+        sorted.sort();
+        if let Some(pair) = sorted.windows(2).find(|pair| pair[0] == pair[1]) {
+            return Err(RulesError::DuplicateStartingMarble(pair[0]));
+        }
+        if self.scoring_interval == 1 {
+            return Err(RulesError::ScoringEveryMarble);
+        }
+        Ok(())
+    }
+
+    /// Calculate what the highest player's score is
+    pub fn winning_score(&self) -> Result<usize, RulesError> {
+        let record = self.game_record()?;
+        Ok(record.scores[record.winner])
+    }
+
+    /// Plays a whole game, keeping track of every player's score and every
+    /// time someone scored
+    pub fn game_record(&self) -> Result<GameRecord, RulesError> {
+        self.play(|_marble| ())
+    }
+
+    /// Plays a whole game, calling `progress` with the marble number
+    /// every 100,000 marbles
+    pub fn play<F: FnMut(usize)>(&self, mut progress: F) -> Result<GameRecord, RulesError> {
+        self.validate()?;
+        let mut marbles = Circle::new(&self.starting_marbles, self.last_marble);
+        let mut scores: Vec<usize> = vec![0; self.players];
+        let mut scoring_turns: Vec<ScoringTurn> = vec![];
+        let first_marble = self.first_marble();
+
+        for i in first_marble..=self.last_marble {
+            if i % PROGRESS_INTERVAL == 0 { progress(i) };
+
+            if let Some(removed) = self.take_turn(&mut marbles, i) {
+                let player = (i - first_marble) % self.players;
+                scores[player] += i + removed;
+                scoring_turns.push(ScoringTurn { marble: i, removed, player });
+            }
+        }
+
+        // Ties go to the earliest player
+        let winner = (0..self.players).rev().max_by_key(|&player| scores[player]).unwrap();
+        Ok(GameRecord { scores, winner, scoring_turns })
+    }
+
+    /// Plays marbles up through `turn` and shows what the circle looks like
+    pub fn replay(&self, turn: usize) -> Result<CircleState, RulesError> {
+        self.validate()?;
+        let mut marbles = Circle::new(&self.starting_marbles, turn);
+        for i in self.first_marble()..=turn {
+            self.take_turn(&mut marbles, i);
+        }
+        Ok(CircleState { marbles: marbles.to_vec(), current: marbles.current })
+    }
+
+    fn first_marble(&self) -> usize {
+        self.starting_marbles.iter().max().unwrap() + 1
+    }
+
+    /// Plays one marble, returning the marble that was taken out if
+    /// this marble scores (and there was one to take)
+    fn take_turn(&self, marbles: &mut Circle, marble: usize) -> Option<usize> {
+        if marble.is_multiple_of(self.scoring_interval) {
+            marbles.move_counter_clockwise(self.removal_offset);
+            marbles.remove()
+        } else {
+            marbles.move_clockwise(self.insert_offset);
+            marbles.insert(marble);
+            None
+        }
     }
 }
 
+/// Calculate what the highest player's score is
+/// 
+/// These use the puzzle's rules, which only go wrong with no players,
+/// so they panic in that case rather than returning an error.
+pub fn winning_score(number_of_players: usize, max_points: usize) -> usize {
+    Rules::new(number_of_players, max_points).winning_score()
+        .expect("Need at least one player")
+}
+
+/// Calculate what the highest player's score is, calling `progress`
+/// with the marble number every 100,000 marbles
+pub fn winning_score_with_progress<F: FnMut(usize)>(number_of_players: usize, max_points: usize, progress: F) -> usize {
+    let record = Rules::new(number_of_players, max_points).play(progress)
+        .expect("Need at least one player");
+    record.scores[record.winner]
+}
+
+/// Plays a whole game, keeping track of every player's score and every
+/// time someone scored
+pub fn game_record(number_of_players: usize, max_points: usize) -> GameRecord {
+    Rules::new(number_of_players, max_points).game_record()
+        .expect("Need at least one player")
+}
+
+/// Plays marbles up through `turn` and shows what the circle looks like
+pub fn replay(turn: usize) -> CircleState {
+    Rules::new(1, turn).replay(turn).unwrap()
+}
 
 #[cfg(test)]
 mod tests {
@@ -80,4 +342,91 @@ mod tests {
     fn test_part_one_with_30() {
         assert_eq!(37305, winning_score(30, 5807));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_hundred_times_bigger() {
+        let mut checkpoints = vec![];
+        let score = winning_score_with_progress(10, 161800, |marble| checkpoints.push(marble));
+        assert_eq!(74765078, score);
+        assert_eq!(vec![100000], checkpoints);
+    }
+
+    #[test]
+    fn test_game_record() {
+        let record = game_record(9, 25);
+        assert_eq!(32, record.scores[4]);
+        assert_eq!(4, record.winner);
+        assert_eq!(vec![ScoringTurn { marble: 23, removed: 9, player: 4 }], record.scoring_turns);
+    }
+
+    #[test]
+    fn test_replay() {
+        let state = replay(25);
+        assert_eq!(vec![0, 16, 8, 17, 4, 18, 19, 2, 24, 20, 25, 10, 21, 5, 22, 11, 1, 12, 6, 13, 3, 14, 7, 15],
+            state.marbles);
+        assert_eq!(25, state.current);
+
+        assert_eq!(CircleState { marbles: vec![0], current: 0 }, replay(0));
+        assert_eq!(19, replay(23).current);
+    }
+
+    #[test]
+    fn test_custom_rules() {
+        let rules = Rules {
+            scoring_interval: 4,
+            removal_offset: 2,
+            ..Rules::new(2, 8)
+        };
+        let record = rules.game_record().unwrap();
+        assert_eq!(vec![0, 20], record.scores);
+        assert_eq!(vec![2, 6], record.scoring_turns.iter().map(|turn| turn.removed).collect::<Vec<_>>());
+        assert_eq!(vec![0, 1, 7, 3, 5], rules.replay(8).unwrap().marbles);
+    }
+
+    #[test]
+    fn test_starting_marbles() {
+        // Starting with 0 and 1 is just the normal game with one turn done
+        let rules = Rules { starting_marbles: vec![0, 1], ..Rules::new(9, 25) };
+        assert_eq!(Ok(replay(25)), rules.replay(25));
+        assert_eq!(32, rules.game_record().unwrap().scores[3]);
+    }
+
+    #[test]
+    fn test_validate() {
+        assert_eq!(Ok(()), Rules::new(9, 25).validate());
+        assert_eq!(Err(RulesError::NoPlayers), Rules::new(0, 25).validate());
+        assert_eq!(Err(RulesError::NoStartingMarbles),
+            Rules { starting_marbles: vec![], ..Rules::new(2, 5) }.validate());
+        assert_eq!(Err(RulesError::DuplicateStartingMarble(1)),
+            Rules { starting_marbles: vec![0, 1, 2, 1], ..Rules::new(2, 5) }.validate());
+        assert_eq!(Err(RulesError::ScoringEveryMarble),
+            Rules { scoring_interval: 1, ..Rules::new(2, 5) }.validate());
+    }
+
+    #[test]
+    fn test_play_invalid_rules() {
+        let rules = Rules { scoring_interval: 1, ..Rules::new(2, 5) };
+        assert_eq!(Err(RulesError::ScoringEveryMarble), rules.game_record());
+        assert_eq!(Err(RulesError::ScoringEveryMarble), rules.replay(3));
+        assert_eq!(Err(RulesError::NoPlayers), Rules::new(0, 25).winning_score());
+    }
+
+    #[test]
+    #[should_panic(expected = "at least one player")]
+    fn test_no_players() {
+        winning_score(0, 25);
+    }
+
+    #[test]
+    fn test_empty_circle() {
+        let mut circle = Circle::new(&[0, 1], 3);
+        assert_eq!(Some(1), circle.remove());
+        assert_eq!(Some(0), circle.remove());
+        assert_eq!(None, circle.remove());
+        assert_eq!(Vec::<usize>::new(), circle.to_vec());
+
+        circle.move_counter_clockwise(7);
+        circle.insert(3);
+        assert_eq!(vec![3], circle.to_vec());
+    }
+}